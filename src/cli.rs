@@ -0,0 +1,41 @@
+use std::{env, str::FromStr};
+
+/// Command line arguments in `--flag value` form, e.g. `--day 8 --export dot`.
+pub struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    pub fn from_env() -> Self {
+        Args {
+            args: env::args().skip(1).collect(),
+        }
+    }
+
+    /// Leading argument when it is not a flag, e.g. `gen` in `gen --day 8 --size 300`.
+    pub fn command(&self) -> Option<&str> {
+        self.args
+            .first()
+            .filter(|x| !x.starts_with("--"))
+            .map(|x| x.as_str())
+    }
+
+    pub fn value(&self, flag: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .position(|x| x == flag)
+            .and_then(|index| self.args.get(index + 1))
+            .map(|x| x.as_str())
+    }
+
+    /// Value of `flag` parsed as `T`, or `default` when it is missing or does not parse.
+    pub fn parsed<T: FromStr>(&self, flag: &str, default: T) -> T {
+        self.value(flag)
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(default)
+    }
+
+    pub fn has(&self, flag: &str) -> bool {
+        self.args.iter().any(|x| x == flag)
+    }
+}
@@ -1,14 +1,190 @@
-use std::fs;
+use crate::{input, rng::Rng};
+use std::{
+    cmp::Reverse,
+    fs,
+    io::{self, Write},
+    time::Instant,
+};
 
 const NUMBERS: [&str; 9] = [
     "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
 ];
 
-fn parse_row_part_1(row: &str) -> i32 {
-    let first = row.chars().find(|char| char.is_numeric()).unwrap();
-    let last = row.chars().filter(|char| char.is_numeric()).last().unwrap();
+fn parse_row_part_1(row: &str) -> Option<i32> {
+    let first = row.bytes().find(|x| x.is_ascii_digit())?;
+    let last = row.bytes().rev().find(|x| x.is_ascii_digit())?;
 
-    format!("{}{}", first, last).parse::<i32>().unwrap()
+    Some(((first - b'0') * 10 + (last - b'0')) as i32)
+}
+
+/// Spelled out tokens and the digits they stand for. Numerals are always recognised on top of
+/// these.
+struct Vocabulary {
+    words: Vec<(String, u8)>,
+}
+
+impl Vocabulary {
+    /// Keeps the tokens longest first, so the first one matching at a position is the longest.
+    fn new(mut words: Vec<(String, u8)>) -> Self {
+        words.sort_by_key(|(word, _)| Reverse(word.len()));
+
+        Vocabulary { words }
+    }
+
+    fn english() -> Self {
+        Vocabulary::new(
+            NUMBERS
+                .iter()
+                .enumerate()
+                .map(|(index, word)| (word.to_string(), index as u8 + 1))
+                .collect(),
+        )
+    }
+
+    /// Reads one `token=digit` pair per line, e.g. `zero=0` or `drei=3`. Blank lines and lines
+    /// starting with `#` are skipped.
+    fn parse(contents: &str) -> Result<Self, String> {
+        let words = contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (token, digit) = line
+                    .rsplit_once('=')
+                    .ok_or_else(|| format!("Expected token=digit, found '{}'", line))?;
+
+                match digit.trim().parse::<u8>() {
+                    Ok(digit) if digit <= 9 && !token.trim().is_empty() => {
+                        Ok((token.trim().to_string(), digit))
+                    }
+                    _ => Err(format!("Invalid vocabulary entry '{}'", line)),
+                }
+            })
+            .collect::<Result<Vec<(String, u8)>, String>>()?;
+
+        Ok(Vocabulary::new(words))
+    }
+
+    /// Vocabulary from the file at `path`, or the English one-to-nine words without a path.
+    fn load(path: Option<&str>) -> Result<Self, String> {
+        match path {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|error| format!("Cannot read vocabulary {}: {}", path, error))
+                .and_then(|contents| Vocabulary::parse(&contents)),
+            None => Ok(Vocabulary::english()),
+        }
+    }
+
+    /// Digit starting at byte `index`, either a numeral or a token. When several tokens start
+    /// there the longest wins.
+    fn digit_at(&self, row: &[u8], index: usize) -> Option<u8> {
+        if row[index].is_ascii_digit() {
+            return Some(row[index] - b'0');
+        }
+
+        self.words
+            .iter()
+            .find(|(word, _)| {
+                word.as_bytes()[0] == row[index] && row[index..].starts_with(word.as_bytes())
+            })
+            .map(|(_, digit)| *digit)
+    }
+}
+
+/// Scans forwards for the first digit and backwards for the last, so overlapping words like
+/// `eightwo` resolve to 8 at the start and 2 at the end, without allocating.
+fn parse_row_part_2(row: &str, vocabulary: &Vocabulary) -> Option<i32> {
+    let bytes = row.as_bytes();
+    let first = (0..bytes.len()).find_map(|index| vocabulary.digit_at(bytes, index))?;
+    let last = (0..bytes.len())
+        .rev()
+        .find_map(|index| vocabulary.digit_at(bytes, index))?;
+
+    Some((first * 10 + last) as i32)
+}
+
+/// What to do with a calibration line that contains no digit.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum MissingDigits {
+    /// Leave the line out of the total.
+    Skip,
+    /// Count the line with a value of zero.
+    Zero,
+    /// Refuse to produce a total.
+    Fail,
+}
+
+impl MissingDigits {
+    fn parse(policy: &str) -> Result<Self, String> {
+        match policy {
+            "skip" => Ok(MissingDigits::Skip),
+            "zero" => Ok(MissingDigits::Zero),
+            "fail" => Ok(MissingDigits::Fail),
+            _ => Err(format!(
+                "Unknown policy '{}', expected skip, zero or fail",
+                policy
+            )),
+        }
+    }
+}
+
+/// Outcome of decoding every line: the total, how many lines went into it and the (1-based)
+/// numbers of the lines without a digit.
+struct Calibration {
+    total: i64,
+    counted: usize,
+    missing: Vec<usize>,
+}
+
+impl Calibration {
+    fn new() -> Self {
+        Calibration {
+            total: 0,
+            counted: 0,
+            missing: vec![],
+        }
+    }
+
+    /// Accounts for the decoded `value` of line `number`.
+    fn add(&mut self, number: usize, value: Option<i32>, policy: MissingDigits) {
+        match (value, policy) {
+            (Some(value), _) => {
+                self.total += value as i64;
+                self.counted += 1;
+            }
+            (None, MissingDigits::Zero) => {
+                self.counted += 1;
+                self.missing.push(number);
+            }
+            (None, _) => self.missing.push(number),
+        }
+    }
+
+    fn finish(self, policy: MissingDigits) -> Result<Calibration, Calibration> {
+        match policy == MissingDigits::Fail && !self.missing.is_empty() {
+            true => Err(self),
+            false => Ok(self),
+        }
+    }
+}
+
+fn print_calibration(part: i32, calibration: Result<Calibration, Calibration>) {
+    match calibration {
+        Ok(calibration) => {
+            println!("Part {}: {}", part, calibration.total);
+
+            if !calibration.missing.is_empty() {
+                println!(
+                    "Part {}: {} lines counted, no digits on lines {:?}",
+                    part, calibration.counted, calibration.missing
+                );
+            }
+        }
+        Err(calibration) => println!(
+            "Part {}: no digits on lines {:?}",
+            part, calibration.missing
+        ),
+    }
 }
 
 fn find_all_written_numbers(row: &str) -> Vec<(usize, usize)> {
@@ -36,7 +212,9 @@ fn find_all_numeric_numbers(row: &str) -> Vec<(usize, usize)> {
         .collect()
 }
 
-fn parse_row_part_2(row: &str) -> i32 {
+/// Original implementation, collecting and sorting every match. Kept as the baseline for
+/// `benchmark`.
+fn parse_row_part_2_sorted(row: &str) -> i32 {
     let mut nums: Vec<(usize, usize)> = find_all_written_numbers(row);
     let mut numeric_numbers = find_all_numeric_numbers(row);
 
@@ -49,12 +227,191 @@ fn parse_row_part_2(row: &str) -> i32 {
     format!("{}{}", first, last).parse::<i32>().unwrap()
 }
 
-pub fn run() {
+/// Times both part 2 decoders over the input repeated `repeat` times.
+pub fn benchmark(repeat: usize) {
     let file = fs::read_to_string("src/inputs/day1.txt").unwrap();
+    let rows: Vec<&str> = file
+        .lines()
+        .cycle()
+        .take(file.lines().count() * repeat)
+        .collect();
+
+    let started = Instant::now();
+    let sorted: i64 = rows
+        .iter()
+        .map(|row| parse_row_part_2_sorted(row) as i64)
+        .sum();
+    let sorted_time = started.elapsed();
+
+    let vocabulary = Vocabulary::english();
+    let started = Instant::now();
+    let scanned: i64 = rows
+        .iter()
+        .flat_map(|row| parse_row_part_2(row, &vocabulary))
+        .map(|value| value as i64)
+        .sum();
+    let scanned_time = started.elapsed();
+
+    println!("Rows: {}", rows.len());
+    println!("Collect and sort: {} in {:?}", sorted, sorted_time);
+    println!("Single pass scan: {} in {:?}", scanned, scanned_time);
+}
+
+/// Writes `size` random calibration lines: runs of letters mixed with spelled out and numeral
+/// digits, with at least one numeral per line so both parts apply.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    for _ in 0..size {
+        let mut tokens: Vec<String> = (0..rng.range(1, 8))
+            .map(|_| match rng.range(0, 3) {
+                0 => NUMBERS[rng.range(0, NUMBERS.len())].to_string(),
+                1 => rng.range(1, 10).to_string(),
+                _ => (0..rng.range(1, 6))
+                    .map(|_| (b'a' + rng.range(0, 26) as u8) as char)
+                    .collect(),
+            })
+            .collect();
+
+        let index = rng.range(0, tokens.len() + 1);
+        tokens.insert(index, rng.range(1, 10).to_string());
+
+        writeln!(out, "{}", tokens.concat())?;
+    }
+
+    Ok(())
+}
+
+/// Decodes spelled out digits with the vocabulary file at `vocabulary_path` when given.
+/// `on_missing` is the policy for lines without digits: `skip`, `zero` or `fail` (the default).
+pub fn run_with_options(
+    input: Option<&str>,
+    vocabulary_path: Option<&str>,
+    on_missing: Option<&str>,
+) {
+    let options = Vocabulary::load(vocabulary_path).and_then(|vocabulary| {
+        Ok((
+            vocabulary,
+            MissingDigits::parse(on_missing.unwrap_or("fail"))?,
+            input::lines(input.unwrap_or("src/inputs/day1.txt"))?,
+        ))
+    });
+    let (vocabulary, policy, rows) = match options {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    let mut part1 = Calibration::new();
+    let mut part2 = Calibration::new();
+
+    for (index, row) in rows.enumerate() {
+        let row = match row {
+            Ok(row) => row,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+
+        part1.add(index + 1, parse_row_part_1(&row), policy);
+        part2.add(index + 1, parse_row_part_2(&row, &vocabulary), policy);
+    }
+
+    print_calibration(1, part1.finish(policy));
+    print_calibration(2, part2.finish(policy));
+}
+
+pub fn run() {
+    run_with_options(None, None, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_part_2() {
+        let rows = [
+            ("two1nine", 29),
+            ("eightwothree", 83),
+            ("abcone2threexyz", 13),
+            ("xtwone3four", 24),
+            ("4nineeightseven2", 42),
+            ("zoneight234", 14),
+            ("7pqrstsixteen", 76),
+        ];
+        let vocabulary = Vocabulary::english();
+
+        for (row, value) in rows {
+            assert_eq!(parse_row_part_2(row, &vocabulary), Some(value), "{}", row);
+        }
+    }
+
+    #[test]
+    fn overlapping_words_count_at_both_ends() {
+        let vocabulary = Vocabulary::english();
+
+        assert_eq!(parse_row_part_2("eightwo", &vocabulary), Some(82));
+        assert_eq!(parse_row_part_2("oneight", &vocabulary), Some(18));
+        assert_eq!(parse_row_part_2("xxtwonexx", &vocabulary), Some(21));
+        assert_eq!(parse_row_part_2("sevenine", &vocabulary), Some(79));
+        assert_eq!(parse_row_part_2("nineight", &vocabulary), Some(98));
+        assert_eq!(parse_row_part_2("xyz", &vocabulary), None);
+    }
+
+    #[test]
+    fn custom_vocabulary() -> Result<(), String> {
+        let vocabulary = Vocabulary::parse("# German\n\n  drei = 3 \nnull=0\nsieb=1\nsieben=7\n")?;
+
+        assert_eq!(parse_row_part_2("xdrei5null", &vocabulary), Some(30));
+        assert_eq!(parse_row_part_2("siebenx", &vocabulary), Some(77));
+        assert_eq!(parse_row_part_2("one4two", &vocabulary), Some(44));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_vocabulary_entries() {
+        for contents in ["drei", "drei=10", "=3", "drei=x", "zwei=2\ndrei"] {
+            assert!(Vocabulary::parse(contents).is_err(), "{:?}", contents);
+        }
+    }
+
+    /// Whether the policy accepts the lines, with the total, counted lines and missing lines.
+    fn account(values: &[Option<i32>], policy: MissingDigits) -> (bool, i64, usize, Vec<usize>) {
+        let mut calibration = Calibration::new();
+
+        for (index, value) in values.iter().enumerate() {
+            calibration.add(index + 1, *value, policy);
+        }
+
+        match calibration.finish(policy) {
+            Ok(x) => (true, x.total, x.counted, x.missing),
+            Err(x) => (false, x.total, x.counted, x.missing),
+        }
+    }
 
-    let part1: i32 = file.lines().map(parse_row_part_1).sum();
-    let part2: i32 = file.lines().map(parse_row_part_2).sum();
+    #[test]
+    fn missing_digit_policies() -> Result<(), String> {
+        let values = [Some(12), None, Some(30), None];
 
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+        assert_eq!(
+            account(&values, MissingDigits::parse("skip")?),
+            (true, 42, 2, vec![2, 4])
+        );
+        assert_eq!(
+            account(&values, MissingDigits::parse("zero")?),
+            (true, 42, 4, vec![2, 4])
+        );
+        assert_eq!(
+            account(&values, MissingDigits::parse("fail")?),
+            (false, 42, 2, vec![2, 4])
+        );
+        assert_eq!(
+            account(&[Some(12), Some(30)], MissingDigits::Fail),
+            (true, 42, 2, vec![])
+        );
+        assert!(MissingDigits::parse("ignore").is_err());
+        Ok(())
+    }
 }
@@ -1,251 +1,851 @@
-use std::fs;
+use crate::rng::Rng;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    io::{self, Write},
+    time::Instant,
+};
 
 #[derive(PartialEq, Debug, Clone, Copy)]
-enum DIRECTION {
-    NORTH,
-    EAST,
-    SOUTH,
-    WEST,
-}
-
-const DIRECTIONS: [DIRECTION; 4] = [
-    DIRECTION::NORTH,
-    DIRECTION::EAST,
-    DIRECTION::SOUTH,
-    DIRECTION::WEST,
-];
-
-fn value_at_position(
-    grid: &Vec<Vec<char>>,
-    position: (usize, usize),
-) -> Option<(char, (usize, usize))> {
-    match grid.get(position.1)?.get(position.0) {
-        Some(l) => Some((*l, position)),
-        None => None,
-    }
+enum Direction {
+    North,
+    East,
+    South,
+    West,
 }
 
-fn position_at_direction(
-    position: (usize, usize),
-    direction: &DIRECTION,
-) -> Option<(usize, usize)> {
-    match direction {
-        DIRECTION::WEST => {
-            return Some((position.0 + 1, position.1));
+impl Direction {
+    fn mask(&self) -> u8 {
+        match self {
+            Direction::North => NORTH,
+            Direction::East => EAST,
+            Direction::South => SOUTH,
+            Direction::West => WEST,
         }
-        DIRECTION::EAST => {
-            if position.0 == 0 {
-                return None;
-            }
+    }
 
-            return Some((position.0 - 1, position.1));
-        }
-        DIRECTION::SOUTH => {
-            return Some((position.0, position.1 + 1));
+    fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
         }
-        DIRECTION::NORTH => {
-            if position.1 == 0 {
-                return None;
-            }
+    }
+}
 
-            return Some((position.0, position.1 - 1));
-        }
+type Grid = Vec<Vec<char>>;
+/// Loop tiles in walking order, each with the pipe the loop uses through it.
+type Loop = Vec<(char, (usize, usize))>;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West,
+];
+
+const NORTH: u8 = 0b0001;
+const EAST: u8 = 0b0010;
+const SOUTH: u8 = 0b0100;
+const WEST: u8 = 0b1000;
+
+/// Every tile that can appear in a maze, with the directions it opens to. Besides the puzzle's
+/// six pipes this covers crosses and T-junctions so variant mazes load too.
+const PIPES: [(char, u8); 11] = [
+    ('|', NORTH | SOUTH),
+    ('-', EAST | WEST),
+    ('L', NORTH | EAST),
+    ('J', NORTH | WEST),
+    ('7', SOUTH | WEST),
+    ('F', SOUTH | EAST),
+    ('+', NORTH | EAST | SOUTH | WEST),
+    ('┬', EAST | SOUTH | WEST),
+    ('┴', NORTH | EAST | WEST),
+    ('├', NORTH | EAST | SOUTH),
+    ('┤', NORTH | SOUTH | WEST),
+];
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+struct Pipe {
+    connections: u8,
+}
+
+impl Pipe {
+    /// Pipe for a tile character. `S` opens in every direction since its shape is unknown
+    /// until it is inferred; ground and unknown characters open nowhere.
+    fn from_char(c: char) -> Pipe {
+        let connections = match c {
+            'S' => NORTH | EAST | SOUTH | WEST,
+            _ => PIPES.iter().find(|x| x.0 == c).map(|x| x.1).unwrap_or(0),
+        };
+
+        Pipe { connections }
+    }
+
+    fn to_char(self) -> Option<char> {
+        PIPES.iter().find(|x| x.1 == self.connections).map(|x| x.0)
+    }
+
+    fn connects(&self, direction: &Direction) -> bool {
+        self.connections & direction.mask() != 0
+    }
+
+    /// Whether this pipe and `other`, lying in `direction` from it, open into each other.
+    fn links_to(&self, other: &Pipe, direction: &Direction) -> bool {
+        self.connects(direction) && other.connects(&direction.opposite())
     }
 }
 
-fn value_at_direction(
-    grid: &Vec<Vec<char>>,
+fn value_at_position(
+    grid: &[Vec<char>],
     position: (usize, usize),
-    direction: &DIRECTION,
 ) -> Option<(char, (usize, usize))> {
-    match position_at_direction(position, direction) {
-        Some(position) => value_at_position(grid, position),
-        None => None,
+    grid.get(position.1)?
+        .get(position.0)
+        .map(|l| (*l, position))
+}
+
+fn position_at_direction(
+    position: (usize, usize),
+    direction: &Direction,
+) -> Option<(usize, usize)> {
+    match direction {
+        Direction::North => Some((position.0, position.1.checked_sub(1)?)),
+        Direction::East => Some((position.0 + 1, position.1)),
+        Direction::South => Some((position.0, position.1 + 1)),
+        Direction::West => Some((position.0.checked_sub(1)?, position.1)),
     }
 }
 
 fn surrounding_values(
-    grid: &Vec<Vec<char>>,
+    grid: &[Vec<char>],
     position: (usize, usize),
-) -> Vec<(char, (usize, usize), DIRECTION)> {
+) -> Vec<(char, (usize, usize), Direction)> {
     DIRECTIONS
         .iter()
-        .flat_map(
-            |direction| match value_at_direction(&grid, position, direction) {
-                Some(x) => return Some((x.0, x.1, *direction)),
-                None => None,
-            },
-        )
+        .flat_map(|direction| {
+            position_at_direction(position, direction)
+                .and_then(|target| value_at_position(grid, target))
+                .map(|x| (x.0, x.1, *direction))
+        })
         .collect()
 }
 
-fn is_char_any_of(options: Vec<char>, target: char) -> bool {
-    options.iter().any(|x| *x == target)
-}
-
 fn is_possible_direction(
-    target: &(char, (usize, usize), DIRECTION),
+    target: &(char, (usize, usize), Direction),
     current_point: &(char, (usize, usize)),
 ) -> bool {
-    match current_point.0 {
-        'S' => {
-            if is_char_any_of(vec!['F', '7', '|'], target.0) && target.2 == DIRECTION::NORTH {
-                return true;
-            }
+    Pipe::from_char(current_point.0).links_to(&Pipe::from_char(target.0), &target.2)
+}
 
-            if is_char_any_of(vec!['|', 'L', 'J'], target.0) && target.2 == DIRECTION::SOUTH {
-                return true;
-            }
+fn parse_grid(path: &str) -> Result<Vec<Vec<char>>, String> {
+    Ok(fs::read_to_string(path)
+        .map_err(|error| format!("Cannot read {}: {}", path, error))?
+        .lines()
+        .map(|line| line.chars().collect())
+        .collect())
+}
 
-            if is_char_any_of(vec!['-', 'L', 'F'], target.0) && target.2 == DIRECTION::EAST {
-                return true;
-            }
+fn find_start(grid: &[Vec<char>]) -> Result<(usize, usize), String> {
+    grid.iter()
+        .enumerate()
+        .flat_map(|(index, row)| row.iter().position(|x| *x == 'S').map(|p| (p, index)))
+        .last()
+        .ok_or_else(|| "No S in the grid".to_string())
+}
 
-            if is_char_any_of(vec!['-', '7', 'J'], target.0) && target.2 == DIRECTION::WEST {
-                return true;
-            }
+/// Works out which pipe is hidden under `S` from the neighbours that connect back to it.
+/// Exactly two must connect, otherwise the loop through `S` is ambiguous or broken.
+fn infer_start_tile(grid: &[Vec<char>], start_pos: (usize, usize)) -> Result<char, String> {
+    let connections: Vec<Direction> = surrounding_values(grid, start_pos)
+        .iter()
+        .filter(|x| is_possible_direction(x, &('S', start_pos)))
+        .map(|x| x.2)
+        .collect();
 
-            return false;
-        }
-        '|' => {
-            if is_char_any_of(vec!['F', '7', '|'], target.0) && target.2 == DIRECTION::NORTH {
-                return true;
-            }
+    let pipe = Pipe {
+        connections: connections.iter().fold(0, |acc, e| acc | e.mask()),
+    };
+
+    match (connections.len(), pipe.to_char()) {
+        (2, Some(tile)) => Ok(tile),
+        _ => Err(format!(
+            "Cannot infer the tile under S at {:?}: {} neighbours connect to it ({:?})",
+            start_pos,
+            connections.len(),
+            connections
+        )),
+    }
+}
 
-            if is_char_any_of(vec!['J', 'L', '|'], target.0) && target.2 == DIRECTION::SOUTH {
-                return true;
-            }
+/// Replaces `S` with its inferred pipe, returning the grid and the position of `S`.
+fn with_start_tile(mut grid: Grid) -> Result<(Grid, (usize, usize)), String> {
+    let start_pos = find_start(&grid)?;
+
+    grid[start_pos.1][start_pos.0] = infer_start_tile(&grid, start_pos)?;
 
-            return false;
+    Ok((grid, start_pos))
+}
+
+fn load(path: &str) -> Result<(Grid, (usize, usize)), String> {
+    with_start_tile(parse_grid(path)?)
+}
+
+/// Walks the loop from `S` by leaving each tile through the connection it was not entered
+/// from, so every step is constant time. Crosses and T-junctions are passed straight through
+/// when possible. Each tile is returned with the pipe the loop actually uses through it, so a
+/// `┴` crossed east to west comes back as `-`. A walk that hits a broken pipe or repeats a
+/// (tile, heading) state before getting back to `S` is not a loop and is an error.
+fn trace_loop(grid: &[Vec<char>], start_pos: (usize, usize)) -> Result<Loop, String> {
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut visited: Vec<bool> = vec![false; width * grid.len() * DIRECTIONS.len()];
+    let mut tiles: Loop = vec![(grid[start_pos.1][start_pos.0], start_pos)];
+    let mut heading = match DIRECTIONS
+        .iter()
+        .find(|direction| Pipe::from_char(tiles[0].0).connects(direction))
+    {
+        Some(direction) => *direction,
+        None => {
+            return Err(format!(
+                "The pipe under S at {:?} connects nowhere",
+                start_pos
+            ))
         }
-        '-' => {
-            if is_char_any_of(vec!['J', '7', '-'], target.0) && target.2 == DIRECTION::WEST {
-                return true;
-            }
+    };
 
-            if is_char_any_of(vec!['L', 'F', '-'], target.0) && target.2 == DIRECTION::EAST {
-                return true;
+    loop {
+        let (c, position) = *tiles.last().unwrap();
+        let state = (position.1 * width + position.0) * DIRECTIONS.len()
+            + heading.mask().trailing_zeros() as usize;
+
+        if visited[state] {
+            return Err(format!(
+                "The walk from S repeats at {:?} heading {:?} without getting back to S",
+                position, heading
+            ));
+        }
+
+        visited[state] = true;
+
+        let next = match position_at_direction(position, &heading)
+            .and_then(|target| value_at_position(grid, target))
+        {
+            Some(next) if Pipe::from_char(c).links_to(&Pipe::from_char(next.0), &heading) => next,
+            _ => {
+                return Err(format!(
+                    "The loop breaks at {:?} heading {:?}",
+                    position, heading
+                ))
             }
+        };
 
-            return false;
+        if next.1 == start_pos {
+            return Ok(tiles);
         }
-        'L' => {
-            if is_char_any_of(vec!['|', '7', 'F'], target.0) && target.2 == DIRECTION::NORTH {
-                return true;
-            }
 
-            if is_char_any_of(vec!['-', 'J', '7'], target.0) && target.2 == DIRECTION::WEST {
-                return true;
-            }
+        let pipe = Pipe::from_char(next.0);
+        let came_from = heading.opposite();
+        let straight = pipe.connects(&heading) && pipe.connections.count_ones() > 2;
+
+        heading = match straight {
+            true => heading,
+            false => match DIRECTIONS
+                .iter()
+                .find(|direction| **direction != came_from && pipe.connects(direction))
+            {
+                Some(direction) => *direction,
+                None => {
+                    return Err(format!(
+                        "The loop breaks at {:?} heading {:?}",
+                        next.1, heading
+                    ))
+                }
+            },
+        };
 
-            return false;
+        let used = Pipe {
+            connections: came_from.mask() | heading.mask(),
+        };
+        tiles.push((used.to_char().unwrap(), next.1));
+    }
+}
+
+/// Tiles strictly inside the loop, found by casting a ray along each row and counting loop
+/// tiles whose traced pipe connects northwards. Pairs like `L-7` or `F-J` flip the parity once
+/// while `L-J` and `F-7` flip it twice, so tiles reached by squeezing between pipes stay
+/// outside. A loop that passes through a tile twice crosses itself and has no inside.
+fn enclosed_tiles(
+    grid: &[Vec<char>],
+    tiles: &[(char, (usize, usize))],
+) -> Result<HashSet<(usize, usize)>, String> {
+    let mut on_loop: HashMap<(usize, usize), char> = HashMap::new();
+
+    for (c, position) in tiles {
+        if on_loop.insert(*position, *c).is_some() {
+            return Err(format!("The loop crosses itself at {:?}", position));
         }
-        'J' => {
-            if is_char_any_of(vec!['|', '7', 'F'], target.0) && target.2 == DIRECTION::NORTH {
-                return true;
+    }
+
+    let mut enclosed: HashSet<(usize, usize)> = HashSet::new();
+
+    for (y, row) in grid.iter().enumerate() {
+        let mut inside = false;
+
+        for x in 0..row.len() {
+            match on_loop.get(&(x, y)) {
+                Some(c) if Pipe::from_char(*c).connects(&Direction::North) => inside = !inside,
+                Some(_) => {}
+                None if inside => {
+                    enclosed.insert((x, y));
+                }
+                None => {}
             }
+        }
+    }
+
+    Ok(enclosed)
+}
+
+/// Enclosed tile count from the loop's area alone, via the shoelace formula and Pick's theorem.
+fn enclosed_count_by_area(tiles: &[(char, (usize, usize))]) -> i64 {
+    let mut points: Vec<(usize, usize)> = tiles.iter().map(|x| x.1).collect();
+    points.push(tiles[0].1);
 
-            if is_char_any_of(vec!['-', 'F', 'L'], target.0) && target.2 == DIRECTION::EAST {
-                return true;
+    // Shoelace formula
+    let area: i64 = points
+        .windows(2)
+        .fold(0, |acc, e| {
+            let (x1, y1) = e[0];
+            let (x2, y2) = e[1];
+
+            acc + x1 as i64 * y2 as i64 - y1 as i64 * x2 as i64
+        })
+        .abs()
+        / 2;
+
+    // Picks theorem
+    area - (tiles.len() as i64 / 2) + 1
+}
+
+/// Breadth-first distances from `S` along linked pipes. Only loop tiles are visited unless
+/// `whole_network` is set, in which case every pipe reachable from `S` is.
+fn distance_field(
+    grid: &[Vec<char>],
+    tiles: &[(char, (usize, usize))],
+    whole_network: bool,
+) -> HashMap<(usize, usize), usize> {
+    let on_loop: HashSet<(usize, usize)> = tiles.iter().map(|x| x.1).collect();
+    let mut distances: HashMap<(usize, usize), usize> = HashMap::from([(tiles[0].1, 0)]);
+    let mut queue: VecDeque<(char, (usize, usize))> = VecDeque::from([tiles[0]]);
+
+    while let Some(current) = queue.pop_front() {
+        let distance = distances[&current.1];
+
+        for neighbour in surrounding_values(grid, current.1) {
+            if !is_possible_direction(&neighbour, &current)
+                || (!whole_network && !on_loop.contains(&neighbour.1))
+                || distances.contains_key(&neighbour.1)
+            {
+                continue;
             }
 
-            return false;
+            distances.insert(neighbour.1, distance + 1);
+            queue.push_back((neighbour.0, neighbour.1));
         }
-        '7' => {
-            if is_char_any_of(vec!['|', 'J', 'L'], target.0) && target.2 == DIRECTION::SOUTH {
-                return true;
-            }
+    }
+
+    distances
+}
+
+/// The tiles at the greatest distance from `S`, along with that distance. An even loop has a
+/// single farthest tile, an odd one two.
+fn farthest_tiles(distances: &HashMap<(usize, usize), usize>) -> (usize, Vec<(usize, usize)>) {
+    let max_distance = distances.values().max().copied().unwrap_or(0);
+    let mut farthest: Vec<(usize, usize)> = distances
+        .iter()
+        .filter(|x| *x.1 == max_distance)
+        .map(|x| *x.0)
+        .collect();
+
+    farthest.sort();
+
+    (max_distance, farthest)
+}
+
+const RESET: &str = "\x1b[0m";
+const START: &str = "\x1b[1;33m";
+const ENCLOSED: &str = "\x1b[2;42m";
+const OUTSIDE: &str = "\x1b[2;31m";
+
+fn box_drawing(c: char) -> char {
+    match c {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        '+' => '┼',
+        _ => c,
+    }
+}
+
+/// Blue to red ramp from the 256-colour palette, used to shade distances from `S`.
+const HEAT: [u8; 11] = [21, 27, 33, 39, 45, 51, 46, 226, 214, 202, 196];
 
-            if is_char_any_of(vec!['-', 'L', 'F'], target.0) && target.2 == DIRECTION::EAST {
-                return true;
+fn heat_color(distance: usize, max_distance: usize) -> String {
+    let index = distance * (HEAT.len() - 1) / max_distance.max(1);
+
+    format!("\x1b[38;5;{}m", HEAT[index])
+}
+
+/// Draws the loop with box-drawing glyphs, marking the start tile as `S`. With `ansi`, tiles
+/// off the loop keep their original character dimmed and are tinted by whether they are
+/// enclosed; without it they are written as `I` (enclosed) or `.` (outside) so the output is
+/// plain text. Tiles with a `distances` entry are shaded as a heatmap in `ansi` mode.
+fn render(
+    grid: &[Vec<char>],
+    tiles: &[(char, (usize, usize))],
+    enclosed: &HashSet<(usize, usize)>,
+    distances: Option<&HashMap<(usize, usize), usize>>,
+    ansi: bool,
+) -> String {
+    let on_loop: HashSet<(usize, usize)> = tiles.iter().map(|x| x.1).collect();
+    let start_pos = tiles[0].1;
+    let max_distance = distances
+        .and_then(|x| x.values().max().copied())
+        .unwrap_or(0);
+    let mut output = String::new();
+
+    for (y, row) in grid.iter().enumerate() {
+        for (x, c) in row.iter().enumerate() {
+            let position = (x, y);
+            let is_enclosed = enclosed.contains(&position);
+            let distance = distances.and_then(|x| x.get(&position));
+
+            match (ansi, position == start_pos, on_loop.contains(&position)) {
+                (true, true, _) => output.push_str(&format!("{}S{}", START, RESET)),
+                (false, true, _) => output.push('S'),
+                (true, false, _) if distance.is_some() => output.push_str(&format!(
+                    "{}{}{}",
+                    heat_color(*distance.unwrap(), max_distance),
+                    box_drawing(*c),
+                    RESET
+                )),
+                (_, false, true) => output.push(box_drawing(*c)),
+                (true, false, false) => {
+                    let color = if is_enclosed { ENCLOSED } else { OUTSIDE };
+                    output.push_str(&format!("{}{}{}", color, c, RESET));
+                }
+                (false, false, false) => output.push(if is_enclosed { 'I' } else { '.' }),
             }
+        }
+
+        output.push('\n');
+    }
 
-            return false;
+    output
+}
+
+/// Prints the loop to the terminal and, with an `output` path, also writes it as plain text.
+/// `heatmap` shades tiles by their distance from `S`, over the whole pipe network connected
+/// to it when `whole_network` is set.
+pub fn render_loop(input: Option<&str>, output: Option<&str>, heatmap: bool, whole_network: bool) {
+    let (grid, start_pos) = match load(input.unwrap_or("src/inputs/day10.txt")) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            return;
         }
-        'F' => {
-            if is_char_any_of(vec!['|', 'J', 'L'], target.0) && target.2 == DIRECTION::SOUTH {
-                return true;
-            }
+    };
+    let tiles = match trace_loop(&grid, start_pos) {
+        Ok(tiles) => tiles,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let enclosed = enclosed_tiles(&grid, &tiles).unwrap_or_else(|error| {
+        println!("{}", error);
+        HashSet::new()
+    });
+
+    let distances = heatmap.then(|| distance_field(&grid, &tiles, whole_network));
+
+    print!(
+        "{}",
+        render(&grid, &tiles, &enclosed, distances.as_ref(), true)
+    );
+
+    if let Some(distances) = &distances {
+        let (distance, farthest) = farthest_tiles(distances);
+        println!("Farthest: {} steps at {:?}", distance, farthest);
+    }
 
-            if is_char_any_of(vec!['-', 'J', '7'], target.0) && target.2 == DIRECTION::WEST {
-                return true;
-            }
+    if let Some(path) = output {
+        if let Err(error) = fs::write(path, render(&grid, &tiles, &enclosed, None, false)) {
+            println!("Cannot write {}: {}", path, error);
+        }
+    }
+}
+
+/// Lays out a closed path of adjacent positions as a grid of pipes, with `S` on the first
+/// position and ground everywhere else.
+fn grid_from_path(path: &[(usize, usize)], width: usize, height: usize) -> Grid {
+    let mut grid: Grid = vec![vec!['.'; width]; height];
+
+    for (index, position) in path.iter().enumerate() {
+        let previous = path[(index + path.len() - 1) % path.len()];
+        let next = path[(index + 1) % path.len()];
+        let connections = DIRECTIONS
+            .iter()
+            .filter(|direction| {
+                let neighbour = position_at_direction(*position, direction);
+                neighbour == Some(previous) || neighbour == Some(next)
+            })
+            .fold(0, |acc, e| acc | e.mask());
+
+        grid[position.1][position.0] = Pipe { connections }.to_char().unwrap();
+    }
 
-            return false;
+    grid[path[0].1][path[0].0] = 'S';
+    grid
+}
+
+/// A `size`×`size` maze (`size` rounded up to even, at least 2) whose loop snakes through every
+/// tile: along the top row, back and forth over the columns right of the first, then up the
+/// first column.
+fn serpentine_maze(size: usize) -> Grid {
+    let size = (size + size % 2).max(2);
+    let mut path: Vec<(usize, usize)> = (0..size).map(|x| (x, 0)).collect();
+
+    for y in 1..size {
+        match y % 2 {
+            1 => path.extend((1..size).rev().map(|x| (x, y))),
+            _ => path.extend((1..size).map(|x| (x, y))),
         }
-        _ => return false,
+    }
+
+    path.extend((1..size).rev().map(|y| (0, y)));
+
+    grid_from_path(&path, size, size)
+}
+
+/// Times loop tracing and tile enclosure on two generated mazes of about `size`×`size`: the
+/// serpentine, whose loop covers every tile, and a random one with tiles enclosed.
+pub fn benchmark(size: usize) {
+    let mazes = [
+        ("Serpentine", serpentine_maze(size)),
+        ("Random", random_maze(&mut Rng::new(0), size)),
+    ];
+
+    for (name, mut grid) in mazes {
+        let start_pos = find_start(&grid).unwrap();
+
+        grid[start_pos.1][start_pos.0] = infer_start_tile(&grid, start_pos).unwrap();
+
+        let started = Instant::now();
+        let tiles = match trace_loop(&grid, start_pos) {
+            Ok(tiles) => tiles,
+            Err(error) => {
+                println!("{} maze: {}", name, error);
+                continue;
+            }
+        };
+        let traced = started.elapsed();
+
+        let started = Instant::now();
+        let enclosed = enclosed_tiles(&grid, &tiles).unwrap_or_default();
+        let enclosure = started.elapsed();
+
+        println!(
+            "{} maze: {}x{}, loop length {}",
+            name,
+            grid[0].len(),
+            grid.len(),
+            tiles.len()
+        );
+        println!("Trace: {:?}", traced);
+        println!("Enclosed: {} tiles in {:?}", enclosed.len(), enclosure);
     }
 }
 
-fn aperture(row: &Vec<(usize, usize)>) -> Vec<Vec<&(usize, usize)>> {
-    row.iter()
-        .enumerate()
-        .flat_map(|(index, item)| match &row.get(index + 1) {
-            Some(next_item) => Some(vec![item, next_item]),
-            _ => None,
-        })
-        .collect::<Vec<Vec<&(usize, usize)>>>()
+/// Flips the link between the tile at `position` and its neighbour in `direction`.
+fn toggle(connections: &mut [Vec<u8>], position: (usize, usize), direction: Direction) {
+    let neighbour = position_at_direction(position, &direction).unwrap();
+
+    connections[position.1][position.0] ^= direction.mask();
+    connections[neighbour.1][neighbour.0] ^= direction.opposite().mask();
 }
 
-pub fn run() {
-    let grid: Vec<Vec<char>> = fs::read_to_string("src/inputs/day10.txt")
-        .unwrap()
-        .lines()
-        .map(|line| line.chars().collect())
+/// Ground and pipes scattered over the tiles off the generated loop.
+const JUNK: [char; 8] = ['.', '.', '|', '-', 'L', 'J', '7', 'F'];
+
+/// A random `size`×`size` maze (`size` rounded down to a multiple of three, at least 6).
+/// The grid is split into 3×3 blocks and the loop runs around the rim of every block in a
+/// random tree over them, so it bends often and encloses each block's centre tile. About one
+/// block in six is left out of the tree. Tiles off the loop are random pipes or ground, except
+/// next to `S` so its shape can still be inferred.
+fn random_maze(rng: &mut Rng, size: usize) -> Grid {
+    let blocks = (size / 3).max(2);
+    let mut connections = vec![vec![0; blocks * 3]; blocks * 3];
+    let mut in_tree = vec![vec![false; blocks]; blocks];
+    let holes: Vec<Vec<bool>> = (0..blocks)
+        .map(|_| (0..blocks).map(|_| rng.range(0, 6) == 0).collect())
         .collect();
+    let mut frontier = vec![];
+
+    let root = (rng.range(0, blocks), rng.range(0, blocks));
+    frontier.extend(DIRECTIONS.iter().map(|direction| (root, *direction)));
+    in_tree[root.1][root.0] = true;
+
+    // Each tree block gets a ring around its centre. Joining two adjacent blocks swaps two
+    // facing links on their shared side for two links across it, which merges their rings.
+    while !frontier.is_empty() {
+        let index = rng.range(0, frontier.len());
+        let (block, direction) = frontier.swap_remove(index);
+        let next = match position_at_direction(block, &direction) {
+            Some((x, y)) if x < blocks && y < blocks && !in_tree[y][x] && !holes[y][x] => (x, y),
+            _ => continue,
+        };
+
+        in_tree[next.1][next.0] = true;
+        frontier.extend(DIRECTIONS.iter().map(|direction| (next, *direction)));
+
+        let (x, y) = match direction {
+            Direction::East | Direction::South => (block.0 * 3, block.1 * 3),
+            Direction::West | Direction::North => (next.0 * 3, next.1 * 3),
+        };
+        let offset = rng.range(0, 2);
+
+        match direction {
+            Direction::East | Direction::West => {
+                toggle(&mut connections, (x + 2, y + offset), Direction::South);
+                toggle(&mut connections, (x + 3, y + offset), Direction::South);
+                toggle(&mut connections, (x + 2, y + offset), Direction::East);
+                toggle(&mut connections, (x + 2, y + offset + 1), Direction::East);
+            }
+            Direction::South | Direction::North => {
+                toggle(&mut connections, (x + offset, y + 2), Direction::East);
+                toggle(&mut connections, (x + offset, y + 3), Direction::East);
+                toggle(&mut connections, (x + offset, y + 2), Direction::South);
+                toggle(&mut connections, (x + offset + 1, y + 2), Direction::South);
+            }
+        }
+    }
+
+    // Rings go in last: the joins above only flip links, so their order does not matter.
+    for (y, row) in in_tree.iter().enumerate() {
+        for x in (0..blocks).filter(|x| row[*x]) {
+            for index in 0..2 {
+                toggle(&mut connections, (x * 3 + index, y * 3), Direction::East);
+                toggle(
+                    &mut connections,
+                    (x * 3 + index, y * 3 + 2),
+                    Direction::East,
+                );
+                toggle(&mut connections, (x * 3, y * 3 + index), Direction::South);
+                toggle(
+                    &mut connections,
+                    (x * 3 + 2, y * 3 + index),
+                    Direction::South,
+                );
+            }
+        }
+    }
 
-    let start_pos: (usize, usize) = grid
+    let mut grid: Grid = connections
         .iter()
-        .enumerate()
-        .flat_map(|(index, row)| match row.iter().position(|x| *x == 'S') {
-            Some(p) => Some((p, index)),
-            None => None,
+        .map(|row| {
+            row.iter()
+                .map(|connections| match *connections {
+                    0 => *rng.pick(&JUNK),
+                    connections => Pipe { connections }.to_char().unwrap(),
+                })
+                .collect()
         })
-        .last()
-        .unwrap();
+        .collect();
 
-    let mut tiles: Vec<(char, (usize, usize))> = vec![('S', start_pos)];
+    let start = (root.0 * 3, root.1 * 3 + rng.range(0, 3));
 
-    loop {
-        let current = tiles.last().unwrap();
-        let possible: Vec<(char, (usize, usize))> = surrounding_values(&grid, current.1)
-            .iter()
-            .filter(|x| {
-                let exists = tiles.iter().any(|tile| tile.1 == x.1);
+    for (_, position, _) in surrounding_values(&grid, start) {
+        if connections[position.1][position.0] == 0 {
+            grid[position.1][position.0] = '.';
+        }
+    }
+    grid[start.1][start.0] = 'S';
+    grid
+}
 
-                is_possible_direction(&x, &current) && exists == false
-            })
-            .map(|x| (x.0, x.1))
-            .collect();
+/// Writes a random `size`×`size` maze, see `random_maze`.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    for row in random_maze(rng, size) {
+        writeln!(out, "{}", row.iter().collect::<String>())?;
+    }
+
+    Ok(())
+}
 
-        if possible.len() == 0 {
-            break;
+pub fn run_with_input(input: Option<&str>) {
+    let (grid, start_pos) = match load(input.unwrap_or("src/inputs/day10.txt")) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            return;
         }
+    };
+    let tiles = match trace_loop(&grid, start_pos) {
+        Ok(tiles) => tiles,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let (part1, _) = farthest_tiles(&distance_field(&grid, &tiles, false));
+
+    println!("Part 1: {}", part1);
+
+    let enclosed = match enclosed_tiles(&grid, &tiles) {
+        Ok(enclosed) => enclosed,
+        Err(error) => {
+            println!("Part 2: {}", error);
+            return;
+        }
+    };
+    let by_area = enclosed_count_by_area(&tiles);
 
-        let next_tile = possible.first().unwrap();
+    println!("Part 2: {}", enclosed.len());
 
-        tiles.push(next_tile.clone());
+    if enclosed.len() as i64 != by_area {
+        println!(
+            "Warning: Pick's theorem gives {} enclosed tiles, the scanline {}",
+            by_area,
+            enclosed.len()
+        );
     }
+}
 
-    println!("Part 1: {}", tiles.len() / 2);
+pub fn run() {
+    run_with_input(None);
+}
 
-    let points: Vec<(usize, usize)> = tiles.iter().map(|x| x.1).collect();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut vertices = aperture(&points);
-    vertices.push(vec![points.last().unwrap(), &start_pos]);
+    fn grid(rows: &str) -> Grid {
+        rows.lines().map(|x| x.chars().collect()).collect()
+    }
 
-    // Shoelace formula
-    let area: i32 = vertices
-        .iter()
-        .fold(0 as i32, |acc, e| {
-            let (x1, y1) = e[0];
-            let (x2, y2) = e[1];
+    fn enclosed_count(rows: &str) -> Result<usize, String> {
+        let (grid, start_pos) = with_start_tile(grid(rows))?;
+        let tiles = trace_loop(&grid, start_pos)?;
+        let enclosed = enclosed_tiles(&grid, &tiles)?;
 
-            acc + *x1 as i32 * *y2 as i32 - *y1 as i32 * *x2 as i32
-        })
-        .abs()
-        / 2;
+        assert_eq!(enclosed.len() as i64, enclosed_count_by_area(&tiles));
+        Ok(enclosed.len())
+    }
 
-    // Picks theorem
-    println!("Part 2: {}", area - (tiles.len() as i32 / 2) + 1);
+    #[test]
+    fn example_enclosed_tiles() -> Result<(), String> {
+        let simple = "\
+...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........";
+
+        assert_eq!(enclosed_count(simple)?, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn example_squeezing_between_pipes() -> Result<(), String> {
+        let squeezed = "\
+..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........";
+        let larger = "\
+.F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...";
+        let with_junk = "\
+FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L";
+
+        assert_eq!(enclosed_count(squeezed)?, 4);
+        assert_eq!(enclosed_count(larger)?, 8);
+        assert_eq!(enclosed_count(with_junk)?, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn start_tile_needs_exactly_two_connecting_neighbours() {
+        assert_eq!(infer_start_tile(&grid(".|.\n.S-\n..."), (1, 1)), Ok('L'));
+        assert_eq!(infer_start_tile(&grid("...\n-S.\n.|."), (1, 1)), Ok('7'));
+        assert!(infer_start_tile(&grid(".|.\n-S-\n.|."), (1, 1)).is_err());
+        assert!(infer_start_tile(&grid(".|.\n-S.\n.|."), (1, 1)).is_err());
+        assert!(infer_start_tile(&grid("...\n.S-\n..."), (1, 1)).is_err());
+    }
+
+    fn farthest(rows: &str) -> Result<(usize, Vec<(usize, usize)>), String> {
+        let (grid, start_pos) = with_start_tile(grid(rows))?;
+        let tiles = trace_loop(&grid, start_pos)?;
+
+        Ok(farthest_tiles(&distance_field(&grid, &tiles, false)))
+    }
+
+    #[test]
+    fn example_farthest_tiles() -> Result<(), String> {
+        let square = "\
+.....
+.S-7.
+.|.|.
+.L-J.
+.....";
+        let complex = "\
+..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...";
+
+        assert_eq!(farthest(square)?, (4, vec![(3, 3)]));
+        assert_eq!(farthest(complex)?, (8, vec![(4, 2)]));
+        Ok(())
+    }
+
+    #[test]
+    fn farthest_tiles_keeps_every_tie_in_order() {
+        let distances = HashMap::from([((0, 0), 0), ((2, 1), 3), ((1, 0), 1), ((0, 2), 3)]);
+
+        assert_eq!(farthest_tiles(&distances), (3, vec![(0, 2), (2, 1)]));
+        assert_eq!(farthest_tiles(&HashMap::new()), (0, vec![]));
+    }
 }
@@ -1,78 +1,401 @@
-use std::fs;
+use crate::{input, rng::Rng};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+};
 
-fn get_color_value_from_draw(draw: &Vec<&str>, color: &str) -> i32 {
-    match draw.iter().position(|x| x.contains(color)) {
-        Some(index) => draw[index - 1].parse::<i32>().unwrap(),
-        None => 0,
+/// Cube counts by colour, used both for a single draw and for the contents of a bag.
+type Cubes = BTreeMap<String, i32>;
+
+/// Parses a draw like `3 blue, 4 red`. Each entry must be exactly a count and a colour name,
+/// and a colour may only appear once per draw.
+fn parse_draw(draw: &str) -> Result<Cubes, String> {
+    let mut cubes = Cubes::new();
+
+    for entry in draw.split(',') {
+        let (count, color) = match entry.split_whitespace().collect::<Vec<&str>>()[..] {
+            [count, color] => (count, color),
+            _ => return Err(format!("Malformed cube count: '{}'", entry.trim())),
+        };
+
+        let count = count
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid cube count: '{}'", entry.trim()))?;
+
+        if cubes.insert(color.to_string(), count).is_some() {
+            return Err(format!(
+                "Colour '{}' repeated in draw '{}'",
+                color,
+                draw.trim()
+            ));
+        }
     }
+
+    Ok(cubes)
 }
 
 #[derive(Debug)]
 struct Game {
     id: i32,
-    /** red, blue, green */
-    draws: Vec<(i32, i32, i32)>,
+    draws: Vec<Cubes>,
 }
 
 impl Game {
-    fn power_of_cubes(&self) -> i32 {
-        let reds = &self.draws.iter().map(|x| x.0).max().unwrap();
-        let blues = &self.draws.iter().map(|x| x.1).max().unwrap();
-        let greens = &self.draws.iter().map(|x| x.2).max().unwrap();
+    /// Fewest cubes of each colour that could have produced every draw.
+    fn minimum_bag(&self) -> Cubes {
+        self.draws
+            .iter()
+            .flatten()
+            .fold(Cubes::new(), |mut acc, (color, count)| {
+                let max = acc.entry(color.clone()).or_insert(0);
+                *max = (*max).max(*count);
+                acc
+            })
+    }
+
+    /// Product of the minimum number of cubes of each of `colors`. A colour never drawn needs
+    /// no cubes, which makes the power zero.
+    fn power_of_cubes(&self, colors: &[&str]) -> i32 {
+        let minimum = self.minimum_bag();
 
-        reds * blues * greens
+        colors
+            .iter()
+            .map(|color| minimum.get(*color).copied().unwrap_or(0))
+            .product()
     }
 
-    fn is_possible(&self, (reds, blues, greens): (i32, i32, i32)) -> bool {
+    /// Whether every draw fits in `bag`. Colours missing from the bag count as zero cubes.
+    fn is_possible(&self, bag: &Cubes) -> bool {
         self.draws
             .iter()
-            .all(|x| x.0 <= reds && x.1 <= blues && x.2 <= greens)
+            .flatten()
+            .all(|(color, count)| *count <= bag.get(color).copied().unwrap_or(0))
     }
 
-    pub fn new(row: &str) -> Self {
-        let id: i32 = row
-            .split(":")
-            .next()
-            .unwrap()
-            .split(" ")
-            .last()
-            .unwrap()
-            .parse::<i32>()
-            .unwrap();
+    pub fn parse(row: &str) -> Result<Self, String> {
+        let (header, draws) = row
+            .split_once(':')
+            .ok_or_else(|| format!("Missing ':' in game: '{}'", row))?;
+
+        let id: i32 = header
+            .strip_prefix("Game ")
+            .and_then(|x| x.trim().parse::<i32>().ok())
+            .ok_or_else(|| format!("Invalid game header: '{}'", header))?;
 
-        let draws: Vec<(i32, i32, i32)> = row
-            .split(':')
-            .last()
-            .unwrap()
+        let draws: Vec<Cubes> = draws
             .split(';')
-            .map(|x| {
-                let numbers: Vec<&str> = x.split(' ').skip(1).collect();
+            .map(parse_draw)
+            .collect::<Result<Vec<Cubes>, String>>()?;
+
+        Ok(Game { id, draws })
+    }
+}
+
+/// Smallest bag that makes every one of `games` possible.
+fn minimal_bag(games: &[&Game]) -> Cubes {
+    games.iter().flat_map(|game| game.minimum_bag()).fold(
+        Cubes::new(),
+        |mut acc, (color, count)| {
+            let max = acc.entry(color).or_insert(0);
+            *max = (*max).max(count);
+            acc
+        },
+    )
+}
+
+/// Bags with at most `bounds` cubes per colour in which all of `games` are possible. Since
+/// adding cubes never makes a game impossible, the answer is a box: for each colour, every
+/// count from the minimum needed up to its bound. `None` when no bag within bounds works.
+fn feasible_bags(games: &[&Game], bounds: &Cubes) -> Option<BTreeMap<String, (i32, i32)>> {
+    let minimum = minimal_bag(games);
+
+    if minimum.keys().any(|color| !bounds.contains_key(color)) {
+        return None;
+    }
+
+    bounds
+        .iter()
+        .map(|(color, bound)| {
+            let needed = minimum.get(color).copied().unwrap_or(0);
+            (needed <= *bound).then(|| (color.clone(), (needed, *bound)))
+        })
+        .collect()
+}
+
+fn binomial(n: i32, k: i32) -> f64 {
+    if k < 0 || k > n {
+        return 0.0;
+    }
+
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+/// Chance of pulling exactly `draw` when grabbing that many cubes at once from `bag`, i.e.
+/// the multivariate hypergeometric probability.
+fn draw_probability(draw: &Cubes, bag: &Cubes) -> f64 {
+    let drawn: i32 = draw.values().sum();
+    let total: i32 = bag.values().sum();
+
+    if drawn > total || draw.keys().any(|color| !bag.contains_key(color)) {
+        return 0.0;
+    }
+
+    let ways: f64 = bag
+        .iter()
+        .map(|(color, count)| binomial(*count, draw.get(color).copied().unwrap_or(0)))
+        .product();
+
+    ways / binomial(total, drawn)
+}
+
+/// Game ids from a comma separated list such as `1,2,3`.
+fn parse_selection(selection: &str) -> Result<Vec<i32>, String> {
+    selection
+        .split(',')
+        .map(|id| {
+            id.trim()
+                .parse::<i32>()
+                .map_err(|_| format!("Invalid game id '{}'", id.trim()))
+        })
+        .collect()
+}
+
+fn format_cubes(cubes: &Cubes) -> String {
+    cubes
+        .iter()
+        .map(|(color, count)| format!("{}={}", color, count))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Prints bag inference results for the games listed in `selection` (comma separated ids,
+/// all games when absent), with at most `max_cubes` of each colour considered. Draw
+/// probabilities use the bag given by `bag_spec` or `bag_file`, see `read_bag`.
+pub fn analyze(
+    input: Option<&str>,
+    selection: Option<&str>,
+    max_cubes: i32,
+    bag_spec: Option<&str>,
+    bag_file: Option<&str>,
+) {
+    let path = input.unwrap_or("src/inputs/day2.txt");
+    let loaded = fs::read_to_string(path)
+        .map_err(|error| format!("Cannot read {}: {}", path, error))
+        .and_then(|file| parse_games(&file))
+        .and_then(|games| Ok((games, read_bag(bag_spec, bag_file)?)))
+        .and_then(|(games, bag)| Ok((games, bag, selection.map(parse_selection).transpose()?)));
+
+    let (games, bag, ids) = match loaded {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    let selected: Vec<&Game> = games
+        .iter()
+        .filter(|game| ids.as_ref().is_none_or(|ids| ids.contains(&game.id)))
+        .collect();
+
+    let bounds: Cubes = minimal_bag(&games.iter().collect::<Vec<&Game>>())
+        .into_keys()
+        .map(|color| (color, max_cubes))
+        .collect();
+
+    println!(
+        "Minimal bag for the selected games: {}",
+        format_cubes(&minimal_bag(&selected))
+    );
+
+    match feasible_bags(&selected, &bounds) {
+        Some(ranges) => {
+            let count: i64 = ranges
+                .values()
+                .map(|(low, high)| (high - low + 1) as i64)
+                .product();
+            let ranges: Vec<String> = ranges
+                .iter()
+                .map(|(color, (low, high))| format!("{} {}..={}", color, low, high))
+                .collect();
+            println!(
+                "Bags up to {} per colour: {} ({} bags)",
+                max_cubes,
+                ranges.join(", "),
+                count
+            );
+        }
+        None => println!("No bag with at most {} cubes per colour works", max_cubes),
+    }
+
+    println!("Draw probabilities with {}:", format_cubes(&bag));
+    for game in &selected {
+        for (index, draw) in game.draws.iter().enumerate() {
+            println!(
+                "  Game {} draw {}: {} -> {:.6}",
+                game.id,
+                index + 1,
+                format_cubes(draw),
+                draw_probability(draw, &bag)
+            );
+        }
+    }
+}
+
+/// The bag from the puzzle statement.
+const DEFAULT_BAG: &str = "red=12, green=13, blue=14";
+
+/// Parses bag contents given as `colour=count` pairs separated by commas or newlines.
+fn parse_bag(spec: &str) -> Result<Cubes, String> {
+    let mut bag = Cubes::new();
+
+    for entry in spec
+        .split([',', '\n'])
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+    {
+        let (color, count) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Expected colour=count in bag, found '{}'", entry))?;
+        let count = count
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid count in bag entry '{}'", entry))?;
+
+        if bag.insert(color.trim().to_string(), count).is_some() {
+            return Err(format!("Colour '{}' repeated in bag", color.trim()));
+        }
+    }
 
-                let red = get_color_value_from_draw(&numbers, "red");
-                let blue = get_color_value_from_draw(&numbers, "blue");
-                let green = get_color_value_from_draw(&numbers, "green");
+    Ok(bag)
+}
 
-                (red, blue, green)
+/// Bag from an inline `spec`, else from the config file at `path`, else the puzzle's default.
+fn read_bag(spec: Option<&str>, path: Option<&str>) -> Result<Cubes, String> {
+    match (spec, path) {
+        (Some(spec), _) => parse_bag(spec),
+        (None, Some(path)) => fs::read_to_string(path)
+            .map_err(|error| format!("Cannot read bag file {}: {}", path, error))
+            .and_then(|contents| parse_bag(&contents)),
+        (None, None) => parse_bag(DEFAULT_BAG),
+    }
+}
+
+fn parse_games(input: &str) -> Result<Vec<Game>, String> {
+    input.lines().map(Game::parse).collect()
+}
+
+/// Writes `size` random games of one to six draws, each showing one to three colours.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    let mut colors = ["red", "green", "blue"];
+
+    for id in 1..=size {
+        let draws: Vec<String> = (0..rng.range(1, 7))
+            .map(|_| {
+                rng.shuffle(&mut colors);
+                colors[..rng.range(1, 4)]
+                    .iter()
+                    .map(|color| format!("{} {}", rng.range(1, 21), color))
+                    .collect::<Vec<String>>()
+                    .join(", ")
             })
             .collect();
 
-        Game { id, draws }
+        writeln!(out, "Game {}: {}", id, draws.join("; "))?;
     }
+
+    Ok(())
 }
 
-pub fn run() {
-    let file = fs::read_to_string("src/inputs/day2.txt").unwrap();
+/// Solves both parts with the bag given by `bag_spec` or `bag_file`, see `read_bag`.
+pub fn run_with_bag(input: Option<&str>, bag_spec: Option<&str>, bag_file: Option<&str>) {
+    let (rows, bag) = match input::lines(input.unwrap_or("src/inputs/day2.txt"))
+        .and_then(|rows| Ok((rows, read_bag(bag_spec, bag_file)?)))
+    {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
 
-    let games: Vec<Game> = file.lines().map(Game::new).collect();
+    let mut part1: i64 = 0;
+    let mut part2: i64 = 0;
 
-    let part1: i32 = games
-        .iter()
-        .filter(|game| game.is_possible((12, 14, 13)))
-        .map(|game| game.id)
-        .sum();
+    for row in rows {
+        let game = match row.and_then(|row| Game::parse(&row)) {
+            Ok(game) => game,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
 
-    let part2: i32 = games.iter().map(|game| game.power_of_cubes()).sum();
+        if game.is_possible(&bag) {
+            part1 += game.id as i64;
+        }
+        part2 += game.power_of_cubes(&["red", "green", "blue"]) as i64;
+    }
 
     println!("Part 1: {}", part1);
     println!("Part 2: {}", part2);
 }
+
+pub fn run() {
+    run_with_bag(None, None, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
+    fn possible_ids(games: &[Game], bag: &Cubes) -> Vec<i32> {
+        games
+            .iter()
+            .filter(|game| game.is_possible(bag))
+            .map(|game| game.id)
+            .collect()
+    }
+
+    #[test]
+    fn example_possible_games() -> Result<(), String> {
+        let ids = possible_ids(&parse_games(EXAMPLE)?, &parse_bag(DEFAULT_BAG)?);
+
+        assert_eq!(ids, vec![1, 2, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn example_power() -> Result<(), String> {
+        let power: i32 = parse_games(EXAMPLE)?
+            .iter()
+            .map(|game| game.power_of_cubes(&["red", "green", "blue"]))
+            .sum();
+
+        assert_eq!(power, 2286);
+        Ok(())
+    }
+
+    #[test]
+    fn draw_probabilities() -> Result<(), String> {
+        let bag = parse_bag("red=2,blue=2")?;
+
+        assert_eq!(
+            draw_probability(&parse_bag("red=1,blue=1")?, &bag),
+            4.0 / 6.0
+        );
+        assert_eq!(draw_probability(&parse_bag("red=2")?, &bag), 1.0 / 6.0);
+        assert_eq!(draw_probability(&parse_bag("red=3")?, &bag), 0.0);
+        assert_eq!(draw_probability(&parse_bag("red=3,blue=2")?, &bag), 0.0);
+        assert_eq!(draw_probability(&parse_bag("green=1")?, &bag), 0.0);
+        Ok(())
+    }
+}
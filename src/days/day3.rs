@@ -1,154 +1,562 @@
+use crate::rng::Rng;
 use regex::Regex;
-use std::fs;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Write},
+};
 
 #[derive(Debug)]
 struct Part {
     start_index: usize,
     end_index: usize,
     row_index: usize,
-    number: i32,
+    number: i64,
 }
 
 #[derive(Debug)]
 struct Symbol {
     index: usize,
+    row_index: usize,
     symbol: char,
 }
 
-fn check_row(part: &Part, symbols: &Vec<Vec<Symbol>>, row_index: usize) -> bool {
-    let symbol_before_part = symbols[row_index]
-        .iter()
-        .find(|x| x.index == part.start_index.checked_sub(1).unwrap_or(0));
+/// Cells around a part's span in its own row and the rows above and below, clipped at the top
+/// and left edges. Cells past the bottom or right edge simply hold no symbol.
+fn neighbourhood(part: &Part) -> impl Iterator<Item = (usize, usize)> {
+    let columns = part.start_index.saturating_sub(1)..=part.end_index + 1;
 
-    let symbol_after_part = symbols[row_index]
-        .iter()
-        .find(|x| x.index == part.end_index + 1);
-
-    let symbol_inside_part = symbols[row_index]
-        .iter()
-        .find(|x| x.index <= part.end_index && x.index >= part.start_index);
+    (part.row_index.saturating_sub(1)..=part.row_index + 1)
+        .flat_map(move |row| columns.clone().map(move |column| (row, column)))
+}
 
-    symbol_before_part.is_some() || symbol_after_part.is_some() || symbol_inside_part.is_some()
+/// Every number and symbol of an engine schematic, linked both ways by adjacency.
+struct Schematic<'a> {
+    parts: Vec<&'a Part>,
+    symbols: Vec<&'a Symbol>,
+    /// Indices into `symbols` adjacent to each part.
+    part_symbols: Vec<Vec<usize>>,
+    /// Indices into `parts` adjacent to each symbol.
+    symbol_parts: Vec<Vec<usize>>,
 }
 
-fn is_symbol_adjacent(part: &Part, symbols: &Vec<Vec<Symbol>>) -> bool {
-    let same_row = check_row(part, &symbols, part.row_index);
-    let row_above = check_row(part, &symbols, part.row_index.checked_sub(1).unwrap_or(0));
-    let row_below_index = (part.row_index + 1).clamp(0, symbols.len() - 1);
-    let row_below = check_row(part, &symbols, row_below_index);
+impl<'a> Schematic<'a> {
+    fn new(rows_parts: &'a [Vec<Part>], rows_symbols: &'a [Vec<Symbol>]) -> Self {
+        let parts: Vec<&Part> = rows_parts.iter().flatten().collect();
+        let symbols: Vec<&Symbol> = rows_symbols.iter().flatten().collect();
 
-    vec![same_row, row_above, row_below]
-        .iter()
-        .any(|x| x == &true)
-}
+        let positions: HashMap<(usize, usize), usize> = symbols
+            .iter()
+            .enumerate()
+            .map(|(index, symbol)| ((symbol.row_index, symbol.index), index))
+            .collect();
 
-fn row_value(row: &Vec<Part>, symbols: &Vec<Vec<Symbol>>) -> i32 {
-    row.iter()
-        .filter(|part| is_symbol_adjacent(part, symbols))
-        .map(|part| part.number)
-        .sum()
-}
+        let part_symbols: Vec<Vec<usize>> = parts
+            .iter()
+            .map(|part| {
+                neighbourhood(part)
+                    .flat_map(|position| positions.get(&position).copied())
+                    .collect()
+            })
+            .collect();
 
-fn check_row_part_2(symbol: &Symbol, parts: &Vec<Vec<Part>>, row_index: usize) -> Vec<i32> {
-    let value_before_symbol = parts[row_index]
-        .iter()
-        .find(|part| part.end_index == symbol.index - 1)
-        .map(|x| x.number)
-        .unwrap_or_else(|| 0);
+        let mut symbol_parts: Vec<Vec<usize>> = vec![vec![]; symbols.len()];
 
-    let value_after_symbol = parts[row_index]
-        .iter()
-        .find(|part| part.start_index == symbol.index + 1)
-        .map(|x| x.number)
-        .unwrap_or_else(|| 0);
+        for (part_index, adjacent) in part_symbols.iter().enumerate() {
+            for symbol_index in adjacent {
+                symbol_parts[*symbol_index].push(part_index);
+            }
+        }
 
-    let symbol_inside_part = parts[row_index]
-        .iter()
-        .find(|part| symbol.index <= part.end_index && symbol.index >= part.start_index)
-        .map(|x| x.number)
-        .unwrap_or_else(|| 0);
-
-    vec![value_before_symbol, value_after_symbol, symbol_inside_part]
-}
-
-fn row_gear_ratio(row: &Vec<Symbol>, parts: &Vec<Vec<Part>>, row_index: usize) -> i32 {
-    row.iter()
-        .filter(|symbol| symbol.symbol == '*')
-        .map(|symbol| {
-            let same_row = check_row_part_2(symbol, &parts, row_index);
-            let row_above = check_row_part_2(symbol, &parts, row_index.checked_sub(1).unwrap_or(0));
-            let row_below_index = (row_index + 1).clamp(0, parts.len() - 1);
-            let row_below = check_row_part_2(symbol, &parts, row_below_index);
-
-            let adjacent_values: Vec<i32> = same_row
-                .iter()
-                .chain(&row_above)
-                .chain(&row_below)
-                .cloned()
-                .filter(|x| x != &0)
-                .collect();
-
-            if adjacent_values.len() == 2 {
-                return adjacent_values[0] * adjacent_values[1];
+        Schematic {
+            parts,
+            symbols,
+            part_symbols,
+            symbol_parts,
+        }
+    }
+
+    /// `*` symbols touching exactly `count` numbers.
+    fn gears_with(&self, count: usize) -> Vec<usize> {
+        (0..self.symbols.len())
+            .filter(|index| self.symbols[*index].symbol == '*')
+            .filter(|index| self.symbol_parts[*index].len() == count)
+            .collect()
+    }
+
+    fn gear_ratio(&self, symbol_index: usize) -> i64 {
+        self.symbol_parts[symbol_index]
+            .iter()
+            .map(|part_index| self.parts[*part_index].number)
+            .product()
+    }
+
+    /// Sum of the numbers touching each kind of symbol; a number next to two different kinds
+    /// counts towards both.
+    fn sum_by_symbol(&self) -> BTreeMap<char, i64> {
+        let mut sums: BTreeMap<char, i64> = BTreeMap::new();
+
+        for (part, adjacent) in self.parts.iter().zip(&self.part_symbols) {
+            let mut kinds: Vec<char> = adjacent.iter().map(|x| self.symbols[*x].symbol).collect();
+            kinds.sort();
+            kinds.dedup();
+
+            for kind in kinds {
+                *sums.entry(kind).or_insert(0) += part.number;
             }
+        }
 
-            0
-        })
-        .sum()
-}
+        sums
+    }
 
-pub fn run() {
-    let file = fs::read_to_string("src/inputs/day3.txt").unwrap();
+    fn isolated_numbers(&self) -> Vec<&Part> {
+        self.parts
+            .iter()
+            .zip(&self.part_symbols)
+            .filter(|(_, adjacent)| adjacent.is_empty())
+            .map(|(part, _)| *part)
+            .collect()
+    }
+}
 
-    let rows: Vec<&str> = file.lines().collect();
+/// Numbers of each row, spanning char columns like the symbols do. The regex reports byte
+/// offsets, which differ from columns once a row holds a non-ASCII symbol.
+fn parse_parts(rows: &[&str]) -> Vec<Vec<Part>> {
+    let rgx = Regex::new(r"[0-9]+").unwrap();
 
-    let rows_parts: Vec<Vec<Part>> = rows
-        .iter()
+    rows.iter()
         .enumerate()
         .map(|(row_index, row)| {
-            let rgx = Regex::new(r"(\d*)").unwrap();
+            let mut columns: Vec<usize> = vec![0; row.len()];
+            for (column, (byte, _)) in row.char_indices().enumerate() {
+                columns[byte] = column;
+            }
 
             rgx.find_iter(row)
-                .flat_map(|x| {
-                    if x.is_empty() == false {
-                        let part = Part {
-                            start_index: x.start(),
-                            end_index: x.end() - 1,
-                            number: x.as_str().parse::<i32>().unwrap(),
-                            row_index,
-                        };
-
-                        return Some(part);
-                    }
-
-                    None
+                .map(|x| Part {
+                    start_index: columns[x.start()],
+                    end_index: columns[x.end() - 1],
+                    number: x.as_str().parse::<i64>().unwrap(),
+                    row_index,
                 })
                 .collect()
         })
-        .collect();
+        .collect()
+}
 
-    let rows_symbols: Vec<Vec<Symbol>> = rows
-        .iter()
-        .map(|row| {
+fn parse_symbols(rows: &[&str]) -> Vec<Vec<Symbol>> {
+    rows.iter()
+        .enumerate()
+        .map(|(row_index, row)| {
             row.chars()
                 .enumerate()
-                .filter(|(_, symbol)| symbol.is_numeric() == false && symbol != &'.')
-                .map(|(index, symbol)| Symbol { symbol, index })
+                .filter(|(_, symbol)| !symbol.is_ascii_digit() && symbol != &'.')
+                .map(|(index, symbol)| Symbol {
+                    symbol,
+                    index,
+                    row_index,
+                })
                 .collect()
         })
-        .collect();
+        .collect()
+}
 
-    let part1: i32 = rows_parts
+fn solve(rows: &[&str]) -> (i64, i64) {
+    let rows_parts = parse_parts(rows);
+    let rows_symbols = parse_symbols(rows);
+    let schematic = Schematic::new(&rows_parts, &rows_symbols);
+
+    let part1: i64 = schematic
+        .parts
         .iter()
-        .map(|parts| row_value(parts, &rows_symbols))
+        .zip(&schematic.part_symbols)
+        .filter(|(_, adjacent)| !adjacent.is_empty())
+        .map(|(part, _)| part.number)
         .sum();
 
-    let part2: i32 = rows_symbols
+    let part2: i64 = schematic
+        .gears_with(2)
         .iter()
-        .enumerate()
-        .map(|(row_index, row)| row_gear_ratio(row, &rows_parts, row_index))
+        .map(|x| schematic.gear_ratio(*x))
         .sum();
 
+    (part1, part2)
+}
+
+/// Reference answer that looks at the eight cells around every digit and symbol directly,
+/// with explicit bounds checks, to validate `solve`.
+fn solve_brute_force(rows: &[&str]) -> (i64, i64) {
+    let grid: Vec<Vec<char>> = rows.iter().map(|row| row.chars().collect()).collect();
+    let cell = |row: i64, column: i64| -> Option<char> {
+        grid.get(usize::try_from(row).ok()?)?
+            .get(usize::try_from(column).ok()?)
+            .copied()
+    };
+    let is_symbol = |c: Option<char>| c.is_some_and(|c| !c.is_ascii_digit() && c != '.');
+
+    // Start column of the number covering each digit cell, keyed by (row, column).
+    let mut numbers: HashMap<(i64, i64), (i64, i64)> = HashMap::new();
+    let mut part1 = 0;
+
+    for (row, cells) in grid.iter().enumerate() {
+        let mut column = 0;
+
+        while column < cells.len() {
+            if !cells[column].is_ascii_digit() {
+                column += 1;
+                continue;
+            }
+
+            let start = column;
+            while column < cells.len() && cells[column].is_ascii_digit() {
+                column += 1;
+            }
+
+            let text: String = cells[start..column].iter().collect();
+            let number = text.parse::<i64>().unwrap();
+            let (row, start, end) = (row as i64, start as i64, column as i64);
+            let touches_symbol =
+                (start - 1..=end).any(|c| (row - 1..=row + 1).any(|r| is_symbol(cell(r, c))));
+
+            if touches_symbol {
+                part1 += number;
+            }
+
+            for c in start..end {
+                numbers.insert((row, c), (start, number));
+            }
+        }
+    }
+
+    let mut part2 = 0;
+
+    for (row, cells) in grid.iter().enumerate() {
+        for (column, c) in cells.iter().enumerate() {
+            if *c != '*' {
+                continue;
+            }
+
+            let mut adjacent: Vec<(i64, i64, i64)> = vec![];
+
+            for r in row as i64 - 1..=row as i64 + 1 {
+                for c in column as i64 - 1..=column as i64 + 1 {
+                    if let Some((start, number)) = numbers.get(&(r, c)) {
+                        if !adjacent.contains(&(r, *start, *number)) {
+                            adjacent.push((r, *start, *number));
+                        }
+                    }
+                }
+            }
+
+            if adjacent.len() == 2 {
+                part2 += adjacent[0].2 * adjacent[1].2;
+            }
+        }
+    }
+
+    (part1, part2)
+}
+
+const FUZZ_SYMBOLS: [char; 10] = ['*', '*', '*', '#', '+', '$', '/', '=', '%', '@'];
+
+/// A random `width`×`height` schematic. Numbers are capped at three digits and cells are
+/// dense enough that numbers regularly sit on all four borders.
+fn random_schematic(rng: &mut Rng, width: usize, height: usize) -> Vec<String> {
+    (0..height)
+        .map(|_| {
+            let mut row = String::new();
+            let mut digits = 0;
+
+            for _ in 0..width {
+                let c = match rng.range(0, 20) {
+                    0..=8 if digits < 3 => char::from_digit(rng.range(0, 10) as u32, 10).unwrap(),
+                    0..=14 => '.',
+                    _ => *rng.pick(&FUZZ_SYMBOLS),
+                };
+
+                digits = if c.is_ascii_digit() { digits + 1 } else { 0 };
+                row.push(c);
+            }
+
+            row
+        })
+        .collect()
+}
+
+/// Compares `solve` with the brute-force oracle on `iterations` random schematics and prints
+/// the first disagreement found.
+pub fn fuzz(seed: u64, iterations: usize) {
+    let mut rng = Rng::new(seed);
+
+    for iteration in 0..iterations {
+        let width = rng.range(1, 12);
+        let height = rng.range(1, 12);
+        let schematic = random_schematic(&mut rng, width, height);
+        let rows: Vec<&str> = schematic.iter().map(|x| x.as_str()).collect();
+        let expected = solve_brute_force(&rows);
+        let actual = solve(&rows);
+
+        if actual != expected {
+            println!(
+                "Mismatch on iteration {}: expected {:?}, got {:?}",
+                iteration, expected, actual
+            );
+            println!("{}", schematic.join("\n"));
+            return;
+        }
+    }
+
+    println!(
+        "{} random schematics agree with the brute-force oracle",
+        iterations
+    );
+}
+
+/// Prints what the schematic model can answer beyond the two puzzle parts.
+pub fn report(input: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day3.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
+    let rows: Vec<&str> = file.lines().collect();
+    let rows_parts = parse_parts(&rows);
+    let rows_symbols = parse_symbols(&rows);
+    let schematic = Schematic::new(&rows_parts, &rows_symbols);
+
+    println!("Sum of numbers by adjacent symbol:");
+    for (symbol, sum) in schematic.sum_by_symbol() {
+        println!("  {}: {}", symbol, sum);
+    }
+
+    println!("Gears by number of adjacent parts:");
+    for count in 0..=8 {
+        let gears = schematic.gears_with(count);
+
+        if !gears.is_empty() {
+            let ratios: i64 = gears.iter().map(|x| schematic.gear_ratio(*x)).sum();
+            println!("  {}: {} gears, ratio sum {}", count, gears.len(), ratios);
+        }
+    }
+
+    let isolated = schematic.isolated_numbers();
+    println!(
+        "Numbers touching no symbol: {} (sum {})",
+        isolated.len(),
+        isolated.iter().map(|x| x.number).sum::<i64>()
+    );
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Mark {
+    Plain,
+    CountedPart,
+    UncountedPart,
+    Gear,
+}
+
+/// Classifies every cell of the schematic for annotation, indexed by row then column.
+fn cell_marks(rows: &[&str], schematic: &Schematic) -> Vec<Vec<Mark>> {
+    let mut marks: Vec<Vec<Mark>> = rows
+        .iter()
+        .map(|row| vec![Mark::Plain; row.chars().count()])
+        .collect();
+
+    for (part, adjacent) in schematic.parts.iter().zip(&schematic.part_symbols) {
+        let mark = match adjacent.is_empty() {
+            true => Mark::UncountedPart,
+            false => Mark::CountedPart,
+        };
+
+        marks[part.row_index][part.start_index..=part.end_index].fill(mark);
+    }
+
+    for gear in schematic.gears_with(2) {
+        let symbol = schematic.symbols[gear];
+        marks[symbol.row_index][symbol.index] = Mark::Gear;
+    }
+
+    marks
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+fn ansi_style(mark: Mark) -> Option<&'static str> {
+    match mark {
+        Mark::Plain => None,
+        Mark::CountedPart => Some("\x1b[1;32m"),
+        Mark::UncountedPart => Some("\x1b[2m"),
+        Mark::Gear => Some("\x1b[1;30;43m"),
+    }
+}
+
+fn html_class(mark: Mark) -> Option<&'static str> {
+    match mark {
+        Mark::Plain => None,
+        Mark::CountedPart => Some("counted"),
+        Mark::UncountedPart => Some("uncounted"),
+        Mark::Gear => Some("gear"),
+    }
+}
+
+fn html_escape(c: char) -> String {
+    match c {
+        '&' => String::from("&amp;"),
+        '<' => String::from("&lt;"),
+        '>' => String::from("&gt;"),
+        _ => c.to_string(),
+    }
+}
+
+/// Reprints the schematic with counted part numbers highlighted, uncounted numbers dimmed and
+/// gears marked, as ANSI text or as a standalone HTML page.
+fn annotate(rows: &[&str], html: bool) -> String {
+    let rows_parts = parse_parts(rows);
+    let rows_symbols = parse_symbols(rows);
+    let schematic = Schematic::new(&rows_parts, &rows_symbols);
+    let marks = cell_marks(rows, &schematic);
+    let mut output = String::new();
+
+    if html {
+        output.push_str(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<style>\n",
+            "body { background: #111; color: #888; }\n",
+            ".counted { color: #6f6; font-weight: bold; }\n",
+            ".uncounted { color: #555; }\n",
+            ".gear { background: #fc3; color: #000; font-weight: bold; }\n",
+            "</style>\n</head>\n<body>\n<pre>\n"
+        ));
+    }
+
+    for (row, row_marks) in rows.iter().zip(&marks) {
+        for (c, mark) in row.chars().zip(row_marks) {
+            match (html, ansi_style(*mark), html_class(*mark)) {
+                (false, Some(style), _) => {
+                    output.push_str(&format!("{}{}{}", style, c, ANSI_RESET))
+                }
+                (true, _, Some(class)) => output.push_str(&format!(
+                    "<span class=\"{}\">{}</span>",
+                    class,
+                    html_escape(c)
+                )),
+                (true, _, None) => output.push_str(&html_escape(c)),
+                (false, None, _) => output.push(c),
+            }
+        }
+
+        output.push('\n');
+    }
+
+    if html {
+        output.push_str("</pre>\n</body>\n</html>\n");
+    }
+
+    output
+}
+
+/// Prints the annotated schematic to the terminal, or writes it as HTML when `html_output`
+/// names a file.
+pub fn export_annotated(input: Option<&str>, html_output: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day3.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
+    let rows: Vec<&str> = file.lines().collect();
+
+    match html_output {
+        Some(path) => {
+            if let Err(error) = fs::write(path, annotate(&rows, true)) {
+                println!("Cannot write {}: {}", path, error);
+            }
+        }
+        None => print!("{}", annotate(&rows, false)),
+    }
+}
+
+/// Writes a random `size`×`size` schematic.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    for row in random_schematic(rng, size, size) {
+        writeln!(out, "{}", row)?;
+    }
+
+    Ok(())
+}
+
+pub fn run_with_input(input: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day3.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
+    let rows: Vec<&str> = file.lines().collect();
+
+    let (part1, part2) = solve(&rows);
+
     println!("Part 1: {}", part1);
     println!("Part 2: {}", part2);
 }
+
+pub fn run() {
+    run_with_input(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_brute_force_on_random_schematics() {
+        let mut rng = Rng::new(39);
+
+        for _ in 0..1000 {
+            let width = rng.range(1, 12);
+            let height = rng.range(1, 12);
+            let schematic = random_schematic(&mut rng, width, height);
+            let rows: Vec<&str> = schematic.iter().map(|x| x.as_str()).collect();
+
+            assert_eq!(solve(&rows), solve_brute_force(&rows), "{:?}", schematic);
+        }
+    }
+
+    #[test]
+    fn numbers_on_every_border() {
+        let cases: [(&[&str], (i64, i64)); 6] = [
+            // Top and bottom rows
+            (&["12..", ".*..", "..34"], (46, 408)),
+            // Left and right columns
+            (&["5...", "*..7", "6..#"], (18, 30)),
+            // Corners, touching diagonally
+            (&["9.8", ".*.", "7.6"], (30, 0)),
+            // A number spanning the whole row
+            (&["123", "..$"], (123, 0)),
+            // Single cell
+            (&["7"], (0, 0)),
+            // No symbol nearby
+            (&["1....", "....*", "2...."], (0, 0)),
+        ];
+
+        for (rows, expected) in cases {
+            assert_eq!(solve(rows), expected, "{:?}", rows);
+            assert_eq!(solve_brute_force(rows), expected, "{:?}", rows);
+        }
+    }
+
+    #[test]
+    fn non_ascii_symbols_index_by_column() {
+        let rows = ["§12.€", "..*..", "4..5¤"];
+
+        assert_eq!(solve(&rows), (17, 60));
+        assert_eq!(solve_brute_force(&rows), (17, 60));
+        assert_eq!(annotate(&rows, false).lines().count(), 3);
+    }
+}
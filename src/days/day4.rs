@@ -1,4 +1,9 @@
-use std::{collections::HashSet, fs};
+use crate::rng::Rng;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, Write},
+};
 
 #[derive(Debug, Clone)]
 struct Card {
@@ -62,52 +67,267 @@ fn cards_from_rows(rows: Vec<&str>) -> Vec<Card> {
         .collect()
 }
 
-fn card_winnings<'a>(card: &Card, all_cards: &'a Vec<Card>) -> Vec<&'a Card> {
-    let count = card.matches_count();
-    let start_id: usize = (card.id as usize) + 1;
+/// Card ids must run 1, 2, 3, ... in table order, since copies are won by position.
+fn validate_ids(cards: &[Card]) -> Result<(), String> {
+    let mut seen: HashSet<i32> = HashSet::new();
+    let duplicates: Vec<i32> = cards
+        .iter()
+        .filter(|card| !seen.insert(card.id))
+        .map(|card| card.id)
+        .collect();
 
-    (start_id..start_id + count)
-        .map(|id| all_cards.iter().find(|x| x.id as usize == id).unwrap())
-        .collect()
+    if !duplicates.is_empty() {
+        return Err(format!("Duplicate card ids: {:?}", duplicates));
+    }
+
+    match cards
+        .iter()
+        .enumerate()
+        .find(|(index, card)| card.id as usize != index + 1)
+    {
+        Some((index, card)) => Err(format!(
+            "Card ids are not contiguous: expected card {} at line {}, found card {}",
+            index + 1,
+            index + 1,
+            card.id
+        )),
+        None => Ok(()),
+    }
 }
 
-pub fn run() {
-    let file = fs::read_to_string("src/inputs/day4.txt").unwrap();
+/// Copies held of each card (originals included), by position in `cards`. Every card only
+/// ever wins copies of cards after it, so a single forward pass settles each count before it
+/// is used.
+fn card_copies(cards: &[Card]) -> Vec<usize> {
+    let mut copies: Vec<usize> = vec![1; cards.len()];
+
+    for (index, card) in cards.iter().enumerate() {
+        let end = (index + 1 + card.matches_count()).min(cards.len());
+
+        for won in index + 1..end {
+            copies[won] += copies[index];
+        }
+    }
+
+    copies
+}
+
+/// Prints how matches and copies are spread over the cards.
+pub fn report(input: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day4.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
+    let rows: Vec<&str> = file.lines().collect();
+    let cards: Vec<Card> = cards_from_rows(rows);
+
+    if let Err(error) = validate_ids(&cards) {
+        println!("{}", error);
+        return;
+    }
+
+    let copies = card_copies(&cards);
+    let total: usize = copies.iter().sum();
+
+    println!("Match count distribution:");
+    let distribution: BTreeMap<usize, usize> =
+        cards.iter().fold(BTreeMap::new(), |mut acc, card| {
+            *acc.entry(card.matches_count()).or_insert(0) += 1;
+            acc
+        });
+    for (matches, count) in distribution {
+        println!("  {:>2} matches: {} cards", matches, count);
+    }
+
+    println!("Copies per card:");
+    for (card, count) in cards.iter().zip(&copies) {
+        println!(
+            "  Card {:>3}: {:>2} matches, {} copies",
+            card.id,
+            card.matches_count(),
+            count
+        );
+    }
+
+    println!("Largest contributors to the total of {}:", total);
+    let mut contributors: Vec<(&Card, &usize)> = cards.iter().zip(&copies).collect();
+    contributors.sort_by(|a, b| b.1.cmp(a.1));
+    for (card, count) in contributors.iter().take(10) {
+        println!(
+            "  Card {:>3}: {} copies ({:.2}%)",
+            card.id,
+            count,
+            **count as f64 * 100.0 / total as f64
+        );
+    }
+
+    let overflowing: Vec<i32> = cards
+        .iter()
+        .filter(|card| card.id as usize + card.matches_count() > cards.len())
+        .map(|card| card.id)
+        .collect();
+    if !overflowing.is_empty() {
+        println!("Cards winning past the end of the table: {:?}", overflowing);
+    }
+}
+
+/// Match counts drawn for generated cards. Averaging under one match per card keeps the
+/// number of copies from growing exponentially with the number of cards.
+const GENERATED_MATCHES: [usize; 8] = [0, 0, 0, 0, 1, 1, 2, 3];
+
+/// Writes `size` random cards with ten winning numbers and twenty-five numbers each. No card
+/// wins copies past the last one.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    let width = size.to_string().len();
+    let mut numbers: Vec<i32> = (1..100).collect();
+    let join = |numbers: &[i32]| -> String {
+        numbers
+            .iter()
+            .map(|number| format!("{:>2}", number))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+
+    for id in 1..=size {
+        let matches = (*rng.pick(&GENERATED_MATCHES)).min(size - id);
+
+        // The first ten numbers win; the raffle takes `matches` of them and fills up from the
+        // rest.
+        rng.shuffle(&mut numbers);
+        let mut raffle: Vec<i32> = numbers[..matches]
+            .iter()
+            .chain(&numbers[10..35 - matches])
+            .copied()
+            .collect();
+        rng.shuffle(&mut raffle);
+
+        writeln!(
+            out,
+            "Card {:>width$}: {} | {}",
+            id,
+            join(&numbers[..10]),
+            join(&raffle),
+            width = width
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn run_with_input(input: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day4.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
     let rows: Vec<&str> = file.lines().collect();
     let cards: Vec<Card> = cards_from_rows(rows);
 
+    if let Err(error) = validate_ids(&cards) {
+        println!("{}", error);
+        return;
+    }
+
     let part1: i32 = cards.iter().map(|card| card.points()).sum();
     println!("Part 1: {}", part1);
 
-    let won_cards: Vec<&Card> = cards
-        .iter()
-        .map(|card| {
-            let original: Vec<&Card> = card_winnings(card, &cards);
+    let part2: usize = card_copies(&cards).iter().sum();
+
+    println!("Part 2: {}", part2);
+}
+
+pub fn run() {
+    run_with_input(None);
+}
 
-            let mut copies: Vec<&Card> = vec![];
-            let mut previous_iteration: Vec<&Card> = original.clone();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
 
-            while previous_iteration.len() != 0 {
-                let mut temp: Vec<&Card> = previous_iteration
+    /// Cards won by `card`. Ids past the end of the table win nothing.
+    fn card_winnings<'a>(card: &Card, all_cards: &'a [Card]) -> Vec<&'a Card> {
+        let count = card.matches_count();
+        let start_id: usize = (card.id as usize) + 1;
+
+        (start_id..start_id + count)
+            .flat_map(|id| all_cards.iter().find(|x| x.id as usize == id))
+            .collect()
+    }
+
+    /// Plays the cascade out card by card, materialising every won copy. Exponential in the
+    /// worst case, so only used to check `card_copies`.
+    fn simulate_copies(cards: &[Card]) -> Vec<usize> {
+        let won_cards: Vec<&Card> = cards
+            .iter()
+            .flat_map(|card| {
+                let original: Vec<&Card> = card_winnings(card, cards);
+
+                let mut copies: Vec<&Card> = vec![];
+                let mut previous_iteration: Vec<&Card> = original.clone();
+
+                while !previous_iteration.is_empty() {
+                    let mut temp: Vec<&Card> = previous_iteration
+                        .iter()
+                        .flat_map(|card| card_winnings(card, cards))
+                        .collect();
+
+                    previous_iteration = temp.clone();
+                    copies.append(&mut temp);
+                }
+
+                original
                     .iter()
-                    .map(|card| card_winnings(card, &cards))
-                    .flatten()
-                    .collect();
+                    .chain(&copies)
+                    .cloned()
+                    .collect::<Vec<&Card>>()
+            })
+            .collect();
 
-                previous_iteration = temp.clone();
-                copies.append(&mut temp);
-            }
+        let won_counts = won_cards.iter().fold(HashMap::new(), |mut acc, card| {
+            *acc.entry(card.id).or_insert(0) += 1;
+            acc
+        });
 
-            original
-                .iter()
-                .chain(&copies)
-                .cloned()
-                .collect::<Vec<&Card>>()
-        })
-        .flatten()
-        .collect();
+        cards
+            .iter()
+            .map(|card| 1 + won_counts.get(&card.id).unwrap_or(&0))
+            .collect()
+    }
 
-    let part2 = won_cards.len() + cards.len();
+    const EXAMPLE: [&str; 6] = [
+        "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53",
+        "Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19",
+        "Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1",
+        "Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83",
+        "Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36",
+        "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11",
+    ];
 
-    println!("Part 2: {}", part2);
+    #[test]
+    fn copies_match_simulation_on_example() {
+        let cards = cards_from_rows(EXAMPLE.to_vec());
+
+        assert_eq!(card_copies(&cards), vec![1, 2, 4, 8, 14, 1]);
+        assert_eq!(card_copies(&cards), simulate_copies(&cards));
+    }
+
+    #[test]
+    fn copies_match_simulation_when_wins_run_past_the_last_card() {
+        let cards = cards_from_rows(vec![
+            "Card 1: 1 2 3 | 1 2 3",
+            "Card 2: 4 5 6 | 4 5 9",
+            "Card 3: 7 8 9 | 7 8 9",
+        ]);
+
+        assert_eq!(card_copies(&cards), vec![1, 2, 4]);
+        assert_eq!(card_copies(&cards), simulate_copies(&cards));
+    }
 }
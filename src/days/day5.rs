@@ -1,5 +1,9 @@
+use crate::rng::Rng;
 use rayon::prelude::*;
-use std::fs;
+use std::{
+    fs,
+    io::{self, Write},
+};
 
 #[derive(Debug)]
 struct ConversionRange {
@@ -109,8 +113,58 @@ fn run_seed(seed: u64, maps: &Vec<Map>, source_category: &str) -> u64 {
     }
 }
 
-pub fn run() {
-    let file = fs::read_to_string("src/inputs/day5.txt").unwrap();
+const CATEGORIES: [&str; 8] = [
+    "seed",
+    "soil",
+    "fertilizer",
+    "water",
+    "light",
+    "temperature",
+    "humidity",
+    "location",
+];
+
+/// Writes an almanac with `size` seed ranges and `size` disjoint conversion ranges per map,
+/// all within 32 bits. Seed ranges stay under a million values so part 2 remains tractable.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    let seeds: Vec<String> = (0..size)
+        .map(|_| format!("{} {}", rng.range(0, 1 << 32), rng.range(1, 1_000_000)))
+        .collect();
+
+    writeln!(out, "seeds: {}", seeds.join(" "))?;
+
+    for categories in CATEGORIES.windows(2) {
+        writeln!(out)?;
+        writeln!(out, "{}-to-{} map:", categories[0], categories[1])?;
+
+        let mut bounds: Vec<usize> = (0..size * 2).map(|_| rng.range(0, 1 << 32)).collect();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        for range in bounds.chunks_exact(2) {
+            let length = range[1] - range[0];
+            writeln!(
+                out,
+                "{} {} {}",
+                rng.range(0, (1 << 32) - length),
+                range[0],
+                length
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run_with_input(input: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day5.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
     let rows: Vec<&str> = file.lines().collect();
 
     let seeds: Vec<u64> = parse_seeds(rows[0]);
@@ -145,3 +199,7 @@ pub fn run() {
     println!("Part 1: {}", part1);
     println!("Part 2: {}", part2);
 }
+
+pub fn run() {
+    run_with_input(None);
+}
@@ -1,3 +1,9 @@
+use crate::rng::Rng;
+use std::{
+    fs,
+    io::{self, Write},
+};
+
 #[derive(Debug)]
 struct Race {
     time: u64,
@@ -9,39 +15,160 @@ impl Race {
         Race { time, distance }
     }
 
-    fn is_winning_speed(&self, speed: &u64) -> bool {
-        (self.time - speed) * speed > self.distance
+    fn is_winning_speed(&self, speed: u64) -> bool {
+        (self.time - speed) as u128 * speed as u128 > self.distance as u128
     }
 
+    /// Distance grows with speed up to half the race time and mirrors after it, so the winning
+    /// speeds are the range between the slowest winning speed and its mirror. The slowest one
+    /// is found by binary search, which keeps part 2's long race fast.
     fn winning_speeds_count(&self) -> usize {
-        (1..self.time)
-            .filter(|speed| self.is_winning_speed(&speed))
-            .collect::<Vec<u64>>()
-            .len()
+        let (mut low, mut high) = (0, self.time / 2 + 1);
+
+        while low < high {
+            let speed = low + (high - low) / 2;
+            if self.is_winning_speed(speed) {
+                high = speed;
+            } else {
+                low = speed + 1;
+            }
+        }
+
+        if low > self.time / 2 {
+            0
+        } else {
+            (self.time - 2 * low + 1) as usize
+        }
     }
 }
 
-fn number_of_ways_to_win_multiplied(races: &Vec<Race>) -> usize {
+/// `None` when the product overflows, which a few dozen generated races are enough for.
+fn number_of_ways_to_win_multiplied(races: &Vec<Race>) -> Option<usize> {
     races
         .iter()
         .map(|race| race.winning_speeds_count())
-        .reduce(|acc, e| acc * e)
-        .unwrap()
+        .try_fold(1, |acc: usize, e| acc.checked_mul(e))
 }
 
-pub fn run() {
-    let races = vec![
-        Race::new(56, 334),
-        Race::new(71, 1135),
-        Race::new(79, 1350),
-        Race::new(99, 2430),
-    ];
+/// Writes up to `size` random races, each with a record that can be beaten. No race is added
+/// once it would push the part 1 product or the joined part 2 race past 64 bits, so larger
+/// sizes still give an input both parts can answer.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    let mut races: Vec<Race> = vec![];
+    let (mut time, mut distance) = (String::new(), String::new());
+
+    while races.len() < size {
+        let race_time = rng.range(7, 101) as u64;
+        let best = (race_time / 2) * (race_time - race_time / 2);
+        let race = Race::new(race_time, rng.range(0, best as usize) as u64);
+
+        let joined_time = format!("{}{}", time, race.time);
+        let joined_distance = format!("{}{}", distance, race.distance);
+        races.push(race);
+
+        if joined_time.parse::<u64>().is_err()
+            || joined_distance.parse::<u64>().is_err()
+            || number_of_ways_to_win_multiplied(&races).is_none()
+        {
+            races.pop();
+            break;
+        }
+
+        (time, distance) = (joined_time, joined_distance);
+    }
+
+    let (times, distances): (Vec<String>, Vec<String>) = races
+        .iter()
+        .map(|race| {
+            let width = race
+                .distance
+                .to_string()
+                .len()
+                .max(race.time.to_string().len());
+            (
+                format!("  {:>width$}", race.time, width = width),
+                format!("  {:>width$}", race.distance, width = width),
+            )
+        })
+        .unzip();
+
+    writeln!(out, "Time:    {}", times.concat())?;
+    writeln!(out, "Distance:{}", distances.concat())
+}
 
-    let races_part2 = vec![Race::new(56717999, 334113513502430)];
+/// Numbers on the row starting with `label`, and the row read as one number with the spaces
+/// removed, the way part 2 reads it (`None` when that does not fit in a `u64`).
+fn parse_row(file: &str, label: &str) -> Result<(Vec<u64>, Option<u64>), String> {
+    let row = file
+        .lines()
+        .find_map(|line| line.strip_prefix(label))
+        .ok_or_else(|| format!("Missing {} row", label))?;
 
-    let part1 = number_of_ways_to_win_multiplied(&races);
-    let part2 = number_of_ways_to_win_multiplied(&races_part2);
+    let numbers = row
+        .split_whitespace()
+        .map(|x| {
+            x.parse::<u64>()
+                .map_err(|_| format!("Invalid number '{}' in {} row", x, label))
+        })
+        .collect::<Result<Vec<u64>, String>>()?;
+    let joined = row
+        .split_whitespace()
+        .collect::<String>()
+        .parse::<u64>()
+        .ok();
 
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+    Ok((numbers, joined))
+}
+
+pub fn run_with_input(input: Option<&str>) {
+    let path = input.unwrap_or("src/inputs/day6.txt");
+    let file = match fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(error) => {
+            println!("Cannot read {}: {}", path, error);
+            return;
+        }
+    };
+
+    let rows =
+        parse_row(&file, "Time:").and_then(|times| Ok((times, parse_row(&file, "Distance:")?)));
+    let ((times, time), (distances, distance)) = match rows {
+        Ok(rows) => rows,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    if times.is_empty() || times.len() != distances.len() {
+        println!(
+            "Expected one distance per race time, got {} times and {} distances",
+            times.len(),
+            distances.len()
+        );
+        return;
+    }
+
+    let races: Vec<Race> = times
+        .into_iter()
+        .zip(distances)
+        .map(|(time, distance)| Race::new(time, distance))
+        .collect();
+
+    match number_of_ways_to_win_multiplied(&races) {
+        Some(part1) => println!("Part 1: {}", part1),
+        None => println!("Part 1: the product does not fit in 64 bits"),
+    }
+
+    match (time, distance) {
+        (Some(time), Some(distance)) => {
+            let race = Race::new(time, distance);
+            println!("Part 2: {}", race.winning_speeds_count());
+        }
+        _ => println!("Part 2: the joined race does not fit in 64 bits"),
+    }
+}
+
+pub fn run() {
+    run_with_input(None);
 }
@@ -1,4 +1,8 @@
-use std::fs;
+use crate::{input, rng::Rng};
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
 
 const CARDS_PART_1: [(char, i32); 13] = [
     ('A', 13),
@@ -121,9 +125,7 @@ fn parse_card(c: char, part: i32) -> i32 {
     CARDS_PART_2.iter().find(|x| x.0 == c).map(|x| x.1).unwrap()
 }
 
-fn result(rows: &Vec<&str>, part: i32) {
-    let mut hands: Vec<Hand> = rows.iter().map(|row| Hand::parse(row, part)).collect();
-
+fn result(mut hands: Vec<Hand>, part: i32) {
     hands.sort_unstable_by_key(|item| {
         (
             item.hand_type,
@@ -148,10 +150,46 @@ fn result(rows: &Vec<&str>, part: i32) {
     println!("Part {}: {}", part, res);
 }
 
-pub fn run() {
-    let file = fs::read_to_string("src/inputs/day7.txt").unwrap();
-    let rows: Vec<&str> = file.lines().collect();
+/// Writes `size` distinct random hands with bids up to 1000.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    let mut seen = HashSet::new();
+
+    while seen.len() < size.min(CARDS_PART_1.len().pow(5)) {
+        let cards: String = (0..5).map(|_| rng.pick(&CARDS_PART_1).0).collect();
 
-    result(&rows, 1);
-    result(&rows, 2);
+        if seen.insert(cards.clone()) {
+            writeln!(out, "{} {}", cards, rng.range(1, 1001))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Ranking needs every hand, so both parts' hands are parsed in one pass and kept.
+pub fn run_with_input(input: Option<&str>) {
+    let rows = match input::lines(input.unwrap_or("src/inputs/day7.txt")) {
+        Ok(rows) => rows,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    let hands: Result<Vec<(Hand, Hand)>, String> = rows
+        .map(|row| row.map(|row| (Hand::parse(&row, 1), Hand::parse(&row, 2))))
+        .collect();
+    let (hands_part_1, hands_part_2): (Vec<Hand>, Vec<Hand>) = match hands {
+        Ok(hands) => hands.into_iter().unzip(),
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    result(hands_part_1, 1);
+    result(hands_part_2, 2);
+}
+
+pub fn run() {
+    run_with_input(None);
 }
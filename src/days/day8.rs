@@ -1,59 +1,119 @@
-use num::integer::lcm;
-use std::fs;
+use crate::{
+    network::{Edge, Network},
+    rng::Rng,
+};
+use num::integer::Integer;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs, io,
+};
 
 struct Node {
     id: String,
     elements: (String, String),
 }
 
-impl Node {
-    fn get_element(&self, instruction: char) -> String {
-        if instruction == 'L' {
-            return self.elements.0.clone();
-        }
-
-        return self.elements.1.clone();
-    }
-}
-
-fn parse_nodes(rows: &Vec<&str>) -> Vec<Node> {
+/// Parses `AAA = (BBB, CCC)` rows, skipping the instructions and the blank line after them.
+fn parse_nodes(rows: &[&str]) -> Result<Vec<Node>, String> {
     rows.iter()
         .skip(2)
         .map(|row| {
-            let id = row.split("=").next().unwrap().trim();
-            let elements: Vec<String> = row
-                .split("(")
-                .skip(1)
-                .next()
-                .unwrap()
-                .split(",")
-                .map(|x| x.trim().replace(")", ""))
-                .collect();
+            let (id, elements) = row
+                .split_once('=')
+                .ok_or_else(|| format!("Missing '=' in node: '{}'", row))?;
+            let (left, right) = elements
+                .trim()
+                .strip_prefix('(')
+                .and_then(|x| x.strip_suffix(')'))
+                .and_then(|x| x.split_once(','))
+                .ok_or_else(|| format!("Expected (left, right) in node: '{}'", row))?;
 
-            Node {
-                id: id.to_string(),
-                elements: (elements[0].clone(), elements[1].clone()),
+            match (id.trim(), left.trim(), right.trim()) {
+                ("", _, _) | (_, "", _) | (_, _, "") => {
+                    Err(format!("Empty name in node: '{}'", row))
+                }
+                (id, left, right) => Ok(Node {
+                    id: id.to_string(),
+                    elements: (left.to_string(), right.to_string()),
+                }),
             }
         })
         .collect()
 }
 
+/// Parses the `L`/`R` instruction row. The walks cycle through it, so it must not be empty.
+fn parse_instructions(row: &str) -> Result<Vec<Edge>, String> {
+    let instructions = row
+        .trim()
+        .chars()
+        .map(|c| Edge::from_char(c).ok_or_else(|| format!("Unknown instruction '{}'", c)))
+        .collect::<Result<Vec<Edge>, String>>()?;
+
+    match instructions.is_empty() {
+        true => Err("No instructions".to_string()),
+        false => Ok(instructions),
+    }
+}
+
+fn compile_network(nodes: &[Node]) -> Result<Network, String> {
+    let mut network = Network::new();
+
+    for node in nodes {
+        if network
+            .id(&node.id)
+            .is_some_and(|id| network.is_defined(id))
+        {
+            return Err(format!("Node {} defined twice", node.id));
+        }
+
+        network.connect(&node.id, &node.elements.0, &node.elements.1);
+    }
+
+    let undefined: Vec<&str> = network
+        .ids()
+        .filter(|id| !network.is_defined(*id))
+        .map(|id| network.name(id))
+        .collect();
+
+    if !undefined.is_empty() {
+        return Err(format!(
+            "Undefined elements referenced: {}",
+            undefined.join(", ")
+        ));
+    }
+
+    Ok(network)
+}
+
+#[derive(Debug, PartialEq)]
+enum Traversal {
+    Reached(u64),
+    /// A (node, instruction index) state repeated before an ending node was reached, so the
+    /// walk would loop forever.
+    Unreachable {
+        steps: u64,
+    },
+}
+
 fn run_map(
-    starting_node: String,
-    ending_node_pattern: String,
-    nodes: &Vec<Node>,
-    instructions: &Vec<char>,
-) -> u64 {
+    starting_node: u32,
+    ending_nodes: &[bool],
+    network: &Network,
+    instructions: &[Edge],
+) -> Traversal {
+    let mut visited: HashSet<(u32, usize)> = HashSet::new();
     let mut instruction_index = 0;
     let mut steps = 0;
-    let mut current_id = starting_node.clone();
+    let mut current_id = starting_node;
 
-    while current_id.ends_with(&ending_node_pattern) == false {
-        let current_node = nodes.iter().find(|x| x.id == current_id).unwrap();
-        let destination = current_node.get_element(instructions[instruction_index]);
+    while !ending_nodes[current_id as usize] {
+        if !visited.insert((current_id, instruction_index)) {
+            return Traversal::Unreachable { steps };
+        }
 
+        current_id = network.step(current_id, instructions[instruction_index]);
         steps += 1;
-        current_id = destination;
 
         if instruction_index == instructions.len() - 1 {
             instruction_index = 0;
@@ -62,30 +122,460 @@ fn run_map(
         }
     }
 
-    steps
+    Traversal::Reached(steps)
 }
 
-pub fn run() {
-    let file = fs::read_to_string("src/inputs/day8.txt").unwrap();
-    let rows: Vec<&str> = file.lines().collect();
+/// Path of a single ghost through the (node, instruction index) state space. Steps before
+/// `prefix_length` happen once; from there on the walk repeats every `cycle_length` steps.
+#[derive(Debug)]
+struct GhostCycle {
+    starting_node: u32,
+    prefix_length: u64,
+    cycle_length: u64,
+    /// Step counts that land on an ending node, below `prefix_length + cycle_length`.
+    hits: Vec<u64>,
+}
+
+impl GhostCycle {
+    fn detect(
+        starting_node: u32,
+        ending_nodes: &[bool],
+        network: &Network,
+        instructions: &[Edge],
+    ) -> Self {
+        let mut first_seen: HashMap<(u32, usize), u64> = HashMap::new();
+        let mut hits: Vec<u64> = vec![];
+        let mut instruction_index = 0;
+        let mut steps = 0;
+        let mut current_id = starting_node;
+
+        loop {
+            if let Some(seen) = first_seen.insert((current_id, instruction_index), steps) {
+                return GhostCycle {
+                    starting_node,
+                    prefix_length: seen,
+                    cycle_length: steps - seen,
+                    hits,
+                };
+            }
+
+            if ending_nodes[current_id as usize] {
+                hits.push(steps);
+            }
+
+            current_id = network.step(current_id, instructions[instruction_index]);
+            instruction_index = (instruction_index + 1) % instructions.len();
+            steps += 1;
+        }
+    }
+
+    fn is_hit(&self, steps: u64) -> bool {
+        if steps < self.prefix_length {
+            return self.hits.contains(&steps);
+        }
+
+        let offset = self.prefix_length + (steps - self.prefix_length) % self.cycle_length;
+
+        self.hits.contains(&offset)
+    }
+
+    /// Nodes visited by the repeating part of the walk.
+    fn cycle_nodes(&self, network: &Network, instructions: &[Edge]) -> Vec<u32> {
+        let mut current_id = self.starting_node;
+        let mut nodes: Vec<u32> = vec![];
 
-    let instructions: Vec<char> = rows[0].chars().collect();
-    let nodes: Vec<Node> = parse_nodes(&rows);
+        for step in 0..self.prefix_length + self.cycle_length {
+            if step >= self.prefix_length {
+                nodes.push(current_id);
+            }
+
+            let instruction = instructions[step as usize % instructions.len()];
+            current_id = network.step(current_id, instruction);
+        }
+
+        nodes
+    }
+
+    fn prefix_hits(&self) -> impl Iterator<Item = &u64> {
+        self.hits.iter().filter(|hit| **hit < self.prefix_length)
+    }
+
+    fn cycle_hits(&self) -> impl Iterator<Item = &u64> {
+        self.hits.iter().filter(|hit| **hit >= self.prefix_length)
+    }
+}
 
-    let part1 = run_map(
-        String::from("AAA"),
-        String::from("ZZZ"),
-        &nodes,
-        &instructions,
-    );
+/// Merges `x = a1 (mod m1)` and `x = a2 (mod m2)` into a single congruence, for moduli that
+/// are not necessarily coprime. Returns `None` when the two have no common solution.
+fn combine_congruences((a1, m1): (i128, i128), (a2, m2): (i128, i128)) -> Option<(i128, i128)> {
+    let gcd = m1.extended_gcd(&m2);
 
-    let part2: u64 = nodes
+    if (a2 - a1) % gcd.gcd != 0 {
+        return None;
+    }
+
+    let modulus = m1 / gcd.gcd * m2;
+    let k = ((a2 - a1) / gcd.gcd * gcd.x).rem_euclid(m2 / gcd.gcd);
+
+    Some(((a1 + m1 * k).rem_euclid(modulus), modulus))
+}
+
+/// First step count at which every ghost stands on an ending node simultaneously, or `None`
+/// if that never happens.
+fn solve_ghosts(cycles: &[GhostCycle]) -> Option<u64> {
+    // An answer inside some ghost's prefix must be one of that ghost's prefix hits.
+    let from_prefix = cycles
         .iter()
-        .filter(|x| x.id.ends_with("A"))
-        .map(|x| run_map(x.id.clone(), String::from("Z"), &nodes, &instructions))
-        .reduce(|acc, e| lcm(acc, e))
-        .unwrap();
+        .flat_map(|cycle| cycle.prefix_hits())
+        .filter(|steps| cycles.iter().all(|cycle| cycle.is_hit(**steps)))
+        .min()
+        .copied();
+
+    // Past every prefix each ghost is periodic, so combine one hit per ghost with CRT.
+    let congruences = cycles
+        .iter()
+        .fold(vec![(0, 1)], |acc: Vec<(i128, i128)>, cycle| {
+            acc.iter()
+                .flat_map(|congruence| {
+                    cycle.cycle_hits().flat_map(|hit| {
+                        combine_congruences(*congruence, (*hit as i128, cycle.cycle_length as i128))
+                    })
+                })
+                .collect()
+        });
+
+    let earliest = cycles.iter().map(|cycle| cycle.prefix_length).max()? as i128;
+    let from_cycle = congruences
+        .iter()
+        .map(|(remainder, modulus)| match *remainder >= earliest {
+            true => *remainder,
+            false => remainder + (earliest - remainder + modulus - 1) / modulus * modulus,
+        })
+        .min()
+        .map(|steps| steps as u64);
+
+    from_prefix.into_iter().chain(from_cycle).min()
+}
+
+const CYCLE_COLORS: [&str; 6] = [
+    "lightblue",
+    "palegreen",
+    "gold",
+    "plum",
+    "lightsalmon",
+    "lightcyan",
+];
+
+/// Renders the network as a Graphviz digraph. Starting nodes (`..A`) are green boxes, ending
+/// nodes (`..Z`) red double circles, and with `cycles` each ghost's loop gets its own fill.
+fn network_to_dot(
+    network: &Network,
+    instructions: &[Edge],
+    cycles: Option<&[GhostCycle]>,
+) -> String {
+    let mut fills: Vec<Option<&str>> = vec![None; network.len()];
+
+    for (index, cycle) in cycles.unwrap_or(&[]).iter().enumerate() {
+        for id in cycle.cycle_nodes(network, instructions) {
+            fills[id as usize].get_or_insert(CYCLE_COLORS[index % CYCLE_COLORS.len()]);
+        }
+    }
+
+    let mut dot = String::from("digraph network {\n");
+
+    for id in network.ids() {
+        let name = network.name(id);
+        let mut attributes: Vec<String> = vec![];
+
+        if name.ends_with('A') {
+            attributes.push("shape=box, color=green, penwidth=2".to_string());
+        } else if name.ends_with('Z') {
+            attributes.push("shape=doublecircle, color=red, penwidth=2".to_string());
+        }
+
+        if let Some(fill) = fills[id as usize] {
+            attributes.push(format!("style=filled, fillcolor={}", fill));
+        }
+
+        writeln!(dot, "  \"{}\" [{}];", name, attributes.join(", ")).unwrap();
+    }
+
+    for id in network.ids() {
+        for (edge, label) in [(Edge::Left, "L"), (Edge::Right, "R")] {
+            let target = network.name(network.step(id, edge));
+            writeln!(
+                dot,
+                "  \"{}\" -> \"{}\" [label={}];",
+                network.name(id),
+                target,
+                label
+            )
+            .unwrap();
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
 
-    println!("Part 1: {}", part1);
-    println!("Part 2: {}", part2);
+fn parse(file: &str) -> Result<(Vec<Edge>, Network), String> {
+    let rows: Vec<&str> = file.lines().collect();
+
+    let instructions = parse_instructions(rows.first().ok_or("Empty input")?)?;
+    let nodes: Vec<Node> = parse_nodes(&rows)?;
+
+    Ok((instructions, compile_network(&nodes)?))
+}
+
+fn load(path: &str) -> Result<(Vec<Edge>, Network), String> {
+    parse(&fs::read_to_string(path).map_err(|error| format!("Cannot read {}: {}", path, error))?)
+}
+
+fn ghost_cycles(network: &Network, instructions: &[Edge]) -> Vec<GhostCycle> {
+    let ghost_ends = network.mark(|name| name.ends_with('Z'));
+
+    network
+        .ids()
+        .filter(|id| network.name(*id).ends_with('A'))
+        .map(|id| GhostCycle::detect(id, &ghost_ends, network, instructions))
+        .collect()
+}
+
+/// Writes the network as DOT to `output`, or to stdout when no path is given.
+pub fn export_dot(input: Option<&str>, output: Option<&str>, with_cycles: bool) {
+    let (instructions, network) = match load(input.unwrap_or("src/inputs/day8.txt")) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    let cycles = with_cycles.then(|| ghost_cycles(&network, &instructions));
+    let dot = network_to_dot(&network, &instructions, cycles.as_deref());
+
+    match output {
+        Some(path) => {
+            if let Err(error) = fs::write(path, dot) {
+                println!("Cannot write {}: {}", path, error);
+            }
+        }
+        None => print!("{}", dot),
+    }
+}
+
+/// Letters for generated node names. `A` and `Z` are left out so that only ghost starts and
+/// ends carry them.
+const NAME_LETTERS: &[u8] = b"BCDEFGHIJKLMNOPQRSTUVWXY";
+
+const CYCLE_PRIMES: [usize; 10] = [41, 43, 47, 53, 59, 61, 67, 71, 73, 79];
+
+const GENERATED_GHOSTS: usize = 6;
+
+/// Name for the `index`th generated node, at least three letters long.
+fn node_name(mut index: usize) -> String {
+    let mut name = vec![];
+
+    while name.len() < 3 || index > 0 {
+        name.push(NAME_LETTERS[index % NAME_LETTERS.len()]);
+        index /= NAME_LETTERS.len();
+    }
+
+    name.iter().rev().map(|x| *x as char).collect()
+}
+
+/// Writes a network with `size` instructions and clean ghost cycles: each ghost runs a loop of
+/// `size` × p steps for a distinct prime p, reaching its `Z` node exactly once per lap and for
+/// the first time at the end of the first lap. `AAA` to `ZZZ` is one of those ghosts. The edge
+/// not taken from each node leads to a random node of the same loop.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl io::Write) -> io::Result<()> {
+    let size = size.max(1);
+    let instructions: Vec<Edge> = (0..size)
+        .map(|_| *rng.pick(&[Edge::Left, Edge::Right]))
+        .collect();
+    let mut primes = CYCLE_PRIMES;
+    let mut lines = vec![];
+    let mut names = 0;
+
+    rng.shuffle(&mut primes);
+
+    for (ghost, prime) in primes[..GENERATED_GHOSTS].iter().enumerate() {
+        let (start, end) = match ghost {
+            0 => ("AAA".to_string(), "ZZZ".to_string()),
+            _ => {
+                let letter = NAME_LETTERS[ghost] as char;
+                (format!("{0}{0}A", letter), format!("{0}{0}Z", letter))
+            }
+        };
+        let length = size * prime;
+
+        // The node reached after each step of the first lap. The end node sits at the same
+        // instruction as the start, so it continues the same way.
+        let mut lap = vec![start];
+        lap.extend((1..length).map(|_| {
+            names += 1;
+            node_name(names)
+        }));
+        lap.push(end);
+
+        for step in 0..=length {
+            let next = &lap[step % length + 1];
+            let other = &lap[rng.range(1, length + 1)];
+            let (left, right) = match instructions[step % size] {
+                Edge::Left => (next, other),
+                Edge::Right => (other, next),
+            };
+
+            lines.push(format!("{} = ({}, {})", lap[step], left, right));
+        }
+    }
+
+    rng.shuffle(&mut lines);
+
+    let instructions: String = instructions.iter().map(|edge| edge.to_char()).collect();
+    writeln!(out, "{}", instructions)?;
+    writeln!(out)?;
+
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+pub fn run_with_input(input: Option<&str>) {
+    let (instructions, network) = match load(input.unwrap_or("src/inputs/day8.txt")) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    let part1 = network.id("AAA").map(|start| {
+        run_map(
+            start,
+            &network.mark(|name| name == "ZZZ"),
+            &network,
+            &instructions,
+        )
+    });
+
+    let cycles = ghost_cycles(&network, &instructions);
+
+    match part1 {
+        Some(Traversal::Reached(part1)) => println!("Part 1: {}", part1),
+        Some(Traversal::Unreachable { steps }) => {
+            println!(
+                "Part 1: ZZZ unreachable (loop detected after {} steps)",
+                steps
+            )
+        }
+        None => println!("Part 1: starting node AAA missing"),
+    }
+
+    match solve_ghosts(&cycles) {
+        Some(part2) => println!("Part 2: {}", part2),
+        None => println!("Part 2: never"),
+    }
+}
+
+pub fn run() {
+    run_with_input(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps every ghost together until all stand on an ending node, as the puzzle describes.
+    fn simulate_ghosts(network: &Network, instructions: &[Edge], limit: u64) -> Option<u64> {
+        let ends = network.mark(|name| name.ends_with('Z'));
+        let mut ghosts: Vec<u32> = network
+            .ids()
+            .filter(|id| network.name(*id).ends_with('A'))
+            .collect();
+
+        for steps in 0..limit {
+            if ghosts.iter().all(|id| ends[*id as usize]) {
+                return Some(steps);
+            }
+
+            let instruction = instructions[steps as usize % instructions.len()];
+            ghosts = ghosts
+                .iter()
+                .map(|id| network.step(*id, instruction))
+                .collect();
+        }
+
+        None
+    }
+
+    fn ghost_steps(file: &str) -> Result<Option<u64>, String> {
+        let (instructions, network) = parse(file)?;
+        let solved = solve_ghosts(&ghost_cycles(&network, &instructions));
+
+        assert_eq!(solved, simulate_ghosts(&network, &instructions, 1000));
+        Ok(solved)
+    }
+
+    #[test]
+    fn example_ghosts() -> Result<(), String> {
+        let file = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+        assert_eq!(ghost_steps(file)?, Some(6));
+        Ok(())
+    }
+
+    #[test]
+    fn ghosts_with_prefixes_meet_before_the_cycle_lengths_lcm() -> Result<(), String> {
+        // The first ghost loops over BBZ, CCC, DDD after one step and ends on steps 1, 4, 7, ...;
+        // the second loops over FFZ, GGG after two steps and ends on steps 2, 4, 6, ...
+        let file = "L
+
+AAA = (BBZ, BBZ)
+BBZ = (CCC, CCC)
+CCC = (DDD, DDD)
+DDD = (BBZ, BBZ)
+EEA = (HHH, HHH)
+HHH = (FFZ, FFZ)
+FFZ = (GGG, GGG)
+GGG = (FFZ, FFZ)";
+
+        let (instructions, network) = parse(file)?;
+        let cycles = ghost_cycles(&network, &instructions);
+        let shapes: Vec<(u64, u64)> = cycles
+            .iter()
+            .map(|cycle| (cycle.prefix_length, cycle.cycle_length))
+            .collect();
+
+        assert_eq!(shapes, [(1, 3), (2, 2)]);
+        assert_eq!(ghost_steps(file)?, Some(4));
+        Ok(())
+    }
+
+    #[test]
+    fn ghosts_that_never_meet() -> Result<(), String> {
+        // One ghost ends only on odd steps, the other only on even ones.
+        let file = "L
+
+AAA = (BBZ, BBZ)
+BBZ = (CCC, CCC)
+CCC = (BBZ, BBZ)
+DDA = (EEE, EEE)
+EEE = (FFZ, FFZ)
+FFZ = (EEE, EEE)";
+
+        assert_eq!(ghost_steps(file)?, None);
+        Ok(())
+    }
 }
@@ -1,51 +1,296 @@
-use std::fs;
+use crate::{input, rng::Rng};
+use num::{BigInt, BigRational, One, Signed, Zero};
+use std::{
+    fmt,
+    io::{self, Write},
+};
 
-fn aperture(row: &Vec<i32>) -> Vec<Vec<&i32>> {
-    row.iter()
-        .enumerate()
-        .flat_map(|(index, item)| match &row.get(index + 1) {
-            Some(next_item) => Some(vec![item, next_item]),
-            _ => None,
-        })
-        .collect::<Vec<Vec<&i32>>>()
+/// Leading entries of each difference layer (`f(0)`, `Δf(0)`, `Δ²f(0)`, ...) down to the first
+/// all-zero layer, computed exactly so long rows cannot overflow.
+fn forward_differences(row: &[i64]) -> Vec<BigInt> {
+    let mut layer: Vec<BigInt> = row.iter().map(|x| BigInt::from(*x)).collect();
+    let mut leading: Vec<BigInt> = vec![];
+
+    while !layer.iter().all(|x| x.is_zero()) {
+        leading.push(layer[0].clone());
+        layer = layer.windows(2).map(|x| &x[1] - &x[0]).collect();
+    }
+
+    leading
+}
+
+/// Value of the row's difference polynomial at index `k`, where `row[0]` sits at index 0.
+/// Uses Newton's forward-difference form `f(k) = Σ C(k, j) Δʲf(0)`, which holds for any `k`,
+/// so `k = row.len()` is the next value and `k = -1` the previous one.
+fn extrapolate(row: &[i64], k: i64) -> BigInt {
+    let mut binomial = BigInt::from(1);
+    let mut value = BigInt::zero();
+
+    for (j, difference) in forward_differences(row).iter().enumerate() {
+        value += &binomial * difference;
+        binomial = binomial * (k - j as i64) / (j as i64 + 1);
+    }
+
+    value
+}
+
+#[derive(Debug)]
+enum Classification {
+    /// Exact fit `f(n) = Σ coefficients[i] · nⁱ` of minimal degree, with `row[0]` at `n = 0`.
+    Polynomial {
+        degree: usize,
+        coefficients: Vec<BigRational>,
+    },
+    /// The differences only vanish once they run out of entries, so no degree below the
+    /// row length is confirmed by the data.
+    NonPolynomial,
 }
 
-fn row_prediction(row: Vec<i32>, part: i32) -> i32 {
-    let mut sequences: Vec<Vec<i32>> = vec![row];
+fn classify(row: &[i64]) -> Classification {
+    let differences = forward_differences(row);
 
-    while sequences.last().unwrap().iter().all(|x| *x == 0) == false {
-        sequences.push(
-            aperture(sequences.last().unwrap())
-                .iter()
-                .map(|x| x[1] - x[0])
-                .collect(),
-        );
+    if !row.is_empty() && differences.len() == row.len() {
+        return Classification::NonPolynomial;
     }
 
-    sequences.reverse();
-    sequences.iter().fold(0, |acc, e| {
-        if part == 2 {
-            return e.first().unwrap() - acc;
+    // Expand Newton's basis C(n, j) = n(n-1)...(n-j+1) / j! into powers of n.
+    let mut coefficients: Vec<BigRational> = vec![BigRational::zero(); differences.len().max(1)];
+    let mut basis: Vec<BigRational> = vec![BigRational::one()];
+
+    for (j, difference) in differences.iter().enumerate() {
+        for (power, coefficient) in basis.iter().enumerate() {
+            coefficients[power] += coefficient * BigRational::from(difference.clone());
         }
 
-        acc + e.last().unwrap()
-    })
+        let factor = BigRational::from(BigInt::from(j as i64));
+        let divisor = BigRational::from(BigInt::from(j as i64 + 1));
+        let mut next: Vec<BigRational> = vec![BigRational::zero(); basis.len() + 1];
+
+        for (power, coefficient) in basis.iter().enumerate() {
+            next[power + 1] += coefficient / &divisor;
+            next[power] -= coefficient * &factor / &divisor;
+        }
+
+        basis = next;
+    }
+
+    Classification::Polynomial {
+        degree: coefficients.len() - 1,
+        coefficients,
+    }
 }
 
-pub fn run() {
-    let rows: Vec<Vec<i32>> = fs::read_to_string("src/inputs/day9.txt")
-        .unwrap()
-        .lines()
-        .map(|line| {
-            line.split(" ")
-                .flat_map(|x| x.parse::<i32>().ok())
-                .collect::<Vec<i32>>()
-        })
-        .collect();
-
-    let part1: i32 = rows.iter().map(|row| row_prediction(row.clone(), 1)).sum();
-    let part2: i32 = rows.iter().map(|row| row_prediction(row.clone(), 2)).sum();
+impl fmt::Display for Classification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let coefficients = match self {
+            Classification::Polynomial { coefficients, .. } => coefficients,
+            Classification::NonPolynomial => return write!(f, "non-polynomial"),
+        };
+
+        let terms: Vec<(usize, &BigRational)> = coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, coefficient)| !coefficient.is_zero())
+            .collect();
+
+        if terms.is_empty() {
+            return write!(f, "f(n) = 0");
+        }
+
+        write!(f, "f(n) =")?;
+
+        for (index, (power, coefficient)) in terms.iter().enumerate() {
+            let sign = match (index, coefficient.is_negative()) {
+                (0, true) => " -",
+                (0, false) => " ",
+                (_, true) => " - ",
+                (_, false) => " + ",
+            };
+            let magnitude = coefficient.abs();
+            let variable = match power {
+                0 => String::new(),
+                1 => String::from("n"),
+                _ => format!("n^{}", power),
+            };
+
+            match (magnitude.is_one() && *power > 0, magnitude.is_integer()) {
+                (true, _) => write!(f, "{}{}", sign, variable)?,
+                (false, true) if *power == 0 => write!(f, "{}{}", sign, magnitude)?,
+                (false, true) => write!(f, "{}{}{}", sign, magnitude, variable)?,
+                (false, false) if *power == 0 => write!(f, "{}({})", sign, magnitude)?,
+                (false, false) => write!(f, "{}({}){}", sign, magnitude, variable)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints the minimal degree and closed form of every history.
+pub fn classify_rows() {
+    let rows = match input::lines("src/inputs/day9.txt") {
+        Ok(rows) => rows,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    for (index, row) in rows.enumerate() {
+        let row = match row {
+            Ok(row) => parse_row(&row),
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+
+        match classify(&row) {
+            Classification::NonPolynomial => println!("Row {}: non-polynomial", index + 1),
+            classification @ Classification::Polynomial { degree, .. } => {
+                println!("Row {}: degree {}, {}", index + 1, degree, classification)
+            }
+        }
+    }
+}
+
+fn parse_row(line: &str) -> Vec<i64> {
+    line.split(' ')
+        .flat_map(|x| x.parse::<i64>().ok())
+        .collect()
+}
+
+/// Writes `size` random histories of 21 values, each a polynomial of degree below ten with
+/// small integer differences.
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> io::Result<()> {
+    for _ in 0..size {
+        let degree = rng.range(0, 10);
+        let mut differences: Vec<i64> = (0..=degree).map(|_| rng.range(0, 11) as i64 - 5).collect();
+
+        if differences[degree] == 0 {
+            differences[degree] = 1;
+        }
+
+        let mut values = vec![];
+
+        for _ in 0..21 {
+            values.push(differences[0].to_string());
+
+            for layer in 0..degree {
+                differences[layer] += differences[layer + 1];
+            }
+        }
+
+        writeln!(out, "{}", values.join(" "))?;
+    }
+
+    Ok(())
+}
+
+pub fn run_with_input(input: Option<&str>) {
+    let rows = match input::lines(input.unwrap_or("src/inputs/day9.txt")) {
+        Ok(rows) => rows,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+
+    let mut part1 = BigInt::zero();
+    let mut part2 = BigInt::zero();
+
+    for row in rows {
+        let row = match row {
+            Ok(row) => parse_row(&row),
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+
+        part1 += extrapolate(&row, row.len() as i64);
+        part2 += extrapolate(&row, -1);
+    }
 
     println!("Part 1: {}", part1);
     println!("Part 2: {}", part2);
 }
+
+pub fn run() {
+    run_with_input(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_next_and_previous_values() {
+        let rows: [(&[i64], i64, i64); 3] = [
+            (&[0, 3, 6, 9, 12, 15], 18, -3),
+            (&[1, 3, 6, 10, 15, 21], 28, 0),
+            (&[10, 13, 16, 21, 30, 45], 68, 5),
+        ];
+
+        for (row, next, previous) in rows {
+            assert_eq!(extrapolate(row, row.len() as i64), BigInt::from(next));
+            assert_eq!(extrapolate(row, -1), BigInt::from(previous));
+        }
+    }
+
+    #[test]
+    fn extrapolates_at_any_index() {
+        let triangular = [1, 3, 6, 10, 15, 21];
+
+        assert_eq!(extrapolate(&triangular, 100), BigInt::from(5151));
+        assert_eq!(extrapolate(&triangular, -10), BigInt::from(36));
+        assert_eq!(extrapolate(&triangular, 3), BigInt::from(10));
+        assert_eq!(extrapolate(&[7, 7, 7], 1_000_000), BigInt::from(7));
+        assert_eq!(extrapolate(&[], 5), BigInt::zero());
+    }
+
+    fn ratio(numerator: i64, denominator: i64) -> BigRational {
+        BigRational::new(BigInt::from(numerator), BigInt::from(denominator))
+    }
+
+    #[test]
+    fn classifies_with_rational_coefficients() {
+        match classify(&[1, 3, 6, 10, 15, 21]) {
+            Classification::Polynomial {
+                degree,
+                coefficients,
+            } => {
+                assert_eq!(degree, 2);
+                assert_eq!(coefficients, [ratio(1, 1), ratio(3, 2), ratio(1, 2)]);
+            }
+            Classification::NonPolynomial => panic!("triangular numbers are quadratic"),
+        }
+    }
+
+    #[test]
+    fn formats_closed_forms() {
+        let cases: [(&[i64], &str); 6] = [
+            (&[1, 3, 6, 10, 15, 21], "f(n) = (1/2)n^2 + (3/2)n + 1"),
+            (&[0, 3, 6, 9, 12, 15], "f(n) = 3n"),
+            (&[5, 4, 3, 2], "f(n) = -n + 5"),
+            (&[0, -1, -4, -9], "f(n) = -n^2"),
+            (&[4, 4, 4], "f(n) = 4"),
+            (&[0, 0, 0], "f(n) = 0"),
+        ];
+
+        for (row, formula) in cases {
+            assert_eq!(classify(row).to_string(), formula, "{:?}", row);
+        }
+    }
+
+    #[test]
+    fn flags_rows_no_polynomial_fits() {
+        assert!(matches!(
+            classify(&[1, 2, 4, 8, 16]),
+            Classification::NonPolynomial
+        ));
+        assert_eq!(classify(&[1, 2, 4, 8, 16]).to_string(), "non-polynomial");
+        assert!(matches!(classify(&[3]), Classification::NonPolynomial));
+    }
+}
@@ -0,0 +1,19 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+/// Lines of the file at `path`, read through a buffer so only the current line is held in
+/// memory however large the file is. A line that cannot be read, e.g. because it is not valid
+/// UTF-8, comes back as an error naming the line.
+pub fn lines(path: &str) -> Result<impl Iterator<Item = Result<String, String>>, String> {
+    let file = File::open(path).map_err(|error| format!("Cannot read {}: {}", path, error))?;
+    let path = path.to_string();
+
+    Ok(BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(move |(index, line)| {
+            line.map_err(|error| format!("Cannot read {} line {}: {}", path, index + 1, error))
+        }))
+}
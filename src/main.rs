@@ -1,14 +1,141 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+pub mod cli;
 pub mod days;
+pub mod input;
+pub mod network;
+pub mod rng;
+
+fn run_day(day: &str) {
+    match day {
+        "1" => days::day1::run(),
+        "2" => days::day2::run(),
+        "3" => days::day3::run(),
+        "4" => days::day4::run(),
+        "5" => days::day5::run(),
+        "6" => days::day6::run(),
+        "7" => days::day7::run(),
+        "8" => days::day8::run(),
+        "9" => days::day9::run(),
+        "10" => days::day10::run(),
+        _ => println!("Unknown day: {}", day),
+    }
+}
+
+/// Writes a random input of the given `size` for `day` to `output`, or to stdout when no path
+/// is given. The same `seed` always produces the same input.
+fn generate_day(day: &str, size: usize, seed: u64, output: Option<&str>) {
+    let mut rng = rng::Rng::new(seed);
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(error) => {
+                println!("Cannot write {}: {}", path, error);
+                return;
+            }
+        },
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let written = match day {
+        "1" => days::day1::generate(&mut rng, size, &mut out),
+        "2" => days::day2::generate(&mut rng, size, &mut out),
+        "3" => days::day3::generate(&mut rng, size, &mut out),
+        "4" => days::day4::generate(&mut rng, size, &mut out),
+        "5" => days::day5::generate(&mut rng, size, &mut out),
+        "6" => days::day6::generate(&mut rng, size, &mut out),
+        "7" => days::day7::generate(&mut rng, size, &mut out),
+        "8" => days::day8::generate(&mut rng, size, &mut out),
+        "9" => days::day9::generate(&mut rng, size, &mut out),
+        "10" => days::day10::generate(&mut rng, size, &mut out),
+        _ => {
+            println!("Unknown day: {}", day);
+            return;
+        }
+    };
+
+    if let Err(error) = written.and_then(|_| out.flush()) {
+        println!("Cannot write input: {}", error);
+    }
+}
 
 fn main() {
-    //days::day1::run();
-    //days::day2::run();
-    //days::day3::run();
-    //days::day4::run();
-    //days::day5::run();
-    //days::day6::run();
-    //days::day7::run();
-    //days::day8::run();
-    //days::day9::run();
-    days::day10::run();
+    let args = cli::Args::from_env();
+
+    if args.command() == Some("gen") {
+        match args.value("--day") {
+            Some(day) => generate_day(
+                day,
+                args.parsed("--size", 100),
+                args.parsed("--seed", 0),
+                args.value("--output"),
+            ),
+            None => println!("gen needs a --day"),
+        }
+
+        return;
+    }
+
+    if let Some(format) = args.value("--export") {
+        match (args.value("--day"), format) {
+            (Some("8"), "dot") => days::day8::export_dot(
+                args.value("--input"),
+                args.value("--output"),
+                args.has("--cycles"),
+            ),
+            _ => println!("Unsupported export: {}", format),
+        }
+
+        return;
+    }
+
+    match args.value("--day") {
+        Some("1") if args.has("--bench") => days::day1::benchmark(args.parsed("--repeat", 100)),
+        Some("1") => days::day1::run_with_options(
+            args.value("--input"),
+            args.value("--vocabulary"),
+            args.value("--on-missing"),
+        ),
+        Some("2") if args.has("--analyze") => days::day2::analyze(
+            args.value("--input"),
+            args.value("--games"),
+            args.parsed("--max", 20),
+            args.value("--bag"),
+            args.value("--bag-file"),
+        ),
+        Some("2") => days::day2::run_with_bag(
+            args.value("--input"),
+            args.value("--bag"),
+            args.value("--bag-file"),
+        ),
+        Some("3") if args.has("--report") => days::day3::report(args.value("--input")),
+        Some("3") if args.has("--annotate") => {
+            days::day3::export_annotated(args.value("--input"), args.value("--html"))
+        }
+        Some("3") if args.has("--fuzz") => {
+            days::day3::fuzz(args.parsed("--seed", 0), args.parsed("--iterations", 10000))
+        }
+        Some("3") => days::day3::run_with_input(args.value("--input")),
+        Some("4") if args.has("--report") => days::day4::report(args.value("--input")),
+        Some("4") => days::day4::run_with_input(args.value("--input")),
+        Some("5") => days::day5::run_with_input(args.value("--input")),
+        Some("6") => days::day6::run_with_input(args.value("--input")),
+        Some("8") => days::day8::run_with_input(args.value("--input")),
+        Some("7") => days::day7::run_with_input(args.value("--input")),
+        Some("9") if args.has("--classify") => days::day9::classify_rows(),
+        Some("9") => days::day9::run_with_input(args.value("--input")),
+        Some("10") if args.has("--render") => days::day10::render_loop(
+            args.value("--input"),
+            args.value("--output"),
+            args.has("--heatmap"),
+            args.has("--network"),
+        ),
+        Some("10") if args.has("--bench") => days::day10::benchmark(args.parsed("--size", 1000)),
+        Some("10") => days::day10::run_with_input(args.value("--input")),
+        Some(day) => run_day(day),
+        None => days::day10::run(),
+    }
 }
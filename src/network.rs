@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Edge {
+    Left,
+    Right,
+}
+
+impl Edge {
+    pub fn from_char(c: char) -> Option<Edge> {
+        match c {
+            'L' => Some(Edge::Left),
+            'R' => Some(Edge::Right),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Edge::Left => 'L',
+            Edge::Right => 'R',
+        }
+    }
+}
+
+const UNDEFINED: u32 = u32::MAX;
+
+/// Binary network with interned node names. Nodes are addressed by dense `u32` ids and each
+/// node's left/right targets are stored in flat arrays, so a step is a single index lookup.
+#[derive(Debug, Default)]
+pub struct Network {
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    left: Vec<u32>,
+    right: Vec<u32>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Network::default()
+    }
+
+    /// Returns the id for `name`, allocating a new node without edges if it has not been seen.
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.left.push(UNDEFINED);
+        self.right.push(UNDEFINED);
+
+        id
+    }
+
+    pub fn connect(&mut self, name: &str, left: &str, right: &str) -> u32 {
+        let id = self.intern(name);
+        let left = self.intern(left);
+        let right = self.intern(right);
+
+        self.left[id as usize] = left;
+        self.right[id as usize] = right;
+
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> {
+        0..self.names.len() as u32
+    }
+
+    /// Whether the node has had its edges set by `connect`, as opposed to only being referenced.
+    pub fn is_defined(&self, id: u32) -> bool {
+        self.left[id as usize] != UNDEFINED
+    }
+
+    pub fn step(&self, id: u32, edge: Edge) -> u32 {
+        match edge {
+            Edge::Left => self.left[id as usize],
+            Edge::Right => self.right[id as usize],
+        }
+    }
+
+    /// Per-node flags for the nodes whose name satisfies `predicate`, indexed by id.
+    pub fn mark(&self, predicate: impl Fn(&str) -> bool) -> Vec<bool> {
+        self.names.iter().map(|name| predicate(name)).collect()
+    }
+}
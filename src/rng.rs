@@ -0,0 +1,54 @@
+/// Small seeded xorshift64* generator, so generated inputs are reproducible without pulling
+/// in an RNG crate.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        const MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+
+        // The state must never be zero, or xorshift stays at zero forever.
+        Rng {
+            state: match seed ^ MIX {
+                0 => MIX,
+                state => state,
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in `low..high`.
+    pub fn range(&mut self, low: usize, high: usize) -> usize {
+        low + (self.next_u64() % (high - low) as u64) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len())]
+    }
+
+    /// Fisher-Yates shuffle of `items` in place.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            items.swap(index, self.range(0, index + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_cancelling_the_mix_still_advances() {
+        let mut rng = Rng::new(0x9E37_79B9_7F4A_7C15);
+
+        assert_ne!(rng.next_u64(), rng.next_u64());
+    }
+}